@@ -0,0 +1,404 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Duration, FixedOffset};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    registration::{normalize_registration, Registration},
+    types::{notice::Notice, util::street_without_house_number},
+};
+
+/// Number of bytes of the HMAC kept in pseudonyms, hex encoded to twice as many characters
+const PSEUDONYM_BYTES: usize = 16;
+
+/// Minimum length of the secret key in bytes, short keys would make pseudonyms guessable
+pub const MIN_KEY_LENGTH: usize = 32;
+
+/// Replaces words of obfuscated notes
+const REDACTED: &str = "***";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistrationPolicy {
+    Remove,
+    /// Replace the registration with a keyed pseudonym, the same for the same registration and key
+    Pseudonym,
+    /// Like `Pseudonym`, but also keep the prefix of the registration, e.g. "HH", in [SanitizedNotice::registration_prefix]
+    PseudonymWithPrefix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotePolicy {
+    Keep,
+    Remove,
+    /// Redact the words of the notice's registration and words containing digits or an @, which may be other registrations, phone numbers or emails
+    Obfuscate,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnonymizationPolicy {
+    pub registration: RegistrationPolicy,
+    pub note: NotePolicy,
+    /// Size in degrees of the grid coordinates are rounded to, `None` keeps them exact
+    pub coordinate_grid: Option<f64>,
+    /// Length of the buckets timestamps are truncated to, `None` keeps them exact
+    pub time_bucket: Option<Duration>,
+    /// Remove house numbers from streets
+    pub remove_house_number: bool,
+}
+
+/// Pseudonymize registrations keeping the prefix, remove notes, round coordinates to about 100 m and timestamps to 15 minutes
+pub const DEFAULT_ANONYMIZATION_POLICY: AnonymizationPolicy = AnonymizationPolicy {
+    registration: RegistrationPolicy::PseudonymWithPrefix,
+    note: NotePolicy::Remove,
+    coordinate_grid: Some(0.001),
+    time_bucket: Some(Duration::minutes(15)),
+    remove_house_number: true,
+};
+
+/// Notice without personal data, to share datasets e.g. with researchers
+///
+/// Photos are always removed and the token is replaced with a pseudonym.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SanitizedNotice {
+    pub token: String,
+    pub status: String,
+    pub street: String,
+    pub city: String,
+    pub zip: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Pseudonym of the registration, the same for all spelling variants of a registration
+    pub registration: Option<String>,
+    /// Prefix of the registration, only kept with [RegistrationPolicy::PseudonymWithPrefix] and if the registration can be parsed
+    pub registration_prefix: Option<String>,
+    pub color: String,
+    pub brand: String,
+    pub tbnr: String,
+    pub start_date: DateTime<FixedOffset>,
+    pub end_date: DateTime<FixedOffset>,
+    pub note: Option<String>,
+    pub created_at: DateTime<FixedOffset>,
+    pub sent_at: DateTime<FixedOffset>,
+    pub vehicle_empty: bool,
+    pub hazard_lights: bool,
+    pub expired_tuv: bool,
+    pub expired_eco: bool,
+    pub over_2_8_tons: bool,
+}
+
+/// Turns notices into [SanitizedNotice]s following an [AnonymizationPolicy]
+///
+/// Pseudonyms are HMAC-SHA256 of the value keyed with a secret, so they can't be reversed or
+/// recomputed without the key, but stay stable across datasets shared with the same key.
+/// The value is tagged with the name of its field, so equal values of different fields get unrelated pseudonyms.
+pub struct Anonymizer {
+    mac: Hmac<Sha256>,
+    policy: AnonymizationPolicy,
+}
+
+fn round_to_grid(value: f64, grid: f64) -> f64 {
+    let rounded = (value / grid).round() * grid;
+    // remove floating point noise like 53.551000000000002
+    (rounded * 1e9).round() / 1e9
+}
+
+fn truncate_to_bucket(date_time: DateTime<FixedOffset>, bucket: Duration) -> DateTime<FixedOffset> {
+    let bucket_seconds = bucket.num_seconds();
+    if bucket_seconds <= 0 {
+        return date_time;
+    }
+    // buckets are aligned to local time, so e.g. hours start at full hours for any offset
+    let offset_seconds = date_time.offset().local_minus_utc() as i64;
+    let local_seconds = date_time.timestamp() + offset_seconds;
+    let truncated = local_seconds - local_seconds.rem_euclid(bucket_seconds) - offset_seconds;
+    match DateTime::from_timestamp(truncated, 0) {
+        None => date_time,
+        Some(val) => val.with_timezone(date_time.offset()),
+    }
+}
+
+fn obfuscate_note(note: &str, registration: &str) -> String {
+    let registration = match registration.parse::<Registration>() {
+        Ok(val) => val.to_string(),
+        Err(_) => registration.to_uppercase(),
+    };
+    let registration_words: Vec<&str> = registration
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    note.split(' ')
+        .map(|word| {
+            let bare_word = word
+                .trim_matches(|c: char| !c.is_alphanumeric())
+                .to_uppercase();
+            if word.contains('@')
+                || word.chars().any(|c| c.is_ascii_digit())
+                || registration_words.contains(&bare_word.as_str())
+            {
+                REDACTED
+            } else {
+                word
+            }
+        })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+impl Anonymizer {
+    /// Create an anonymizer keyed with the secret `key`, which must be at least [MIN_KEY_LENGTH] bytes long
+    pub fn new(key: &[u8], policy: AnonymizationPolicy) -> Result<Self, anyhow::Error> {
+        if key.len() < MIN_KEY_LENGTH {
+            return Err(anyhow!(
+                "key must be at least {} bytes long, got {}",
+                MIN_KEY_LENGTH,
+                key.len()
+            ));
+        }
+        match Hmac::<Sha256>::new_from_slice(key) {
+            Err(error) => Err(anyhow!(error)),
+            Ok(mac) => Ok(Anonymizer { mac, policy }),
+        }
+    }
+
+    pub fn policy(&self) -> &AnonymizationPolicy {
+        &self.policy
+    }
+
+    /// Hex encoded, truncated HMAC-SHA256 of `value` tagged with the name of its `field`
+    pub fn pseudonym(&self, field: &str, value: &str) -> String {
+        let mut mac = self.mac.clone();
+        mac.update(field.as_bytes());
+        mac.update(b"\0");
+        mac.update(value.as_bytes());
+        mac.finalize().into_bytes()[..PSEUDONYM_BYTES]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Pseudonym of the normalized registration, so spelling variants of a registration share it
+    fn registration(&self, registration: &str) -> Option<String> {
+        match self.policy.registration {
+            RegistrationPolicy::Remove => None,
+            RegistrationPolicy::Pseudonym | RegistrationPolicy::PseudonymWithPrefix => {
+                Some(self.pseudonym("registration", &normalize_registration(registration)))
+            }
+        }
+    }
+
+    fn registration_prefix(&self, registration: &str) -> Option<String> {
+        match self.policy.registration {
+            RegistrationPolicy::PseudonymWithPrefix => match registration.parse::<Registration>() {
+                Err(_) => None,
+                Ok(val) => Some(val.prefix),
+            },
+            _ => None,
+        }
+    }
+
+    fn date_time(&self, date_time: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        match self.policy.time_bucket {
+            None => date_time,
+            Some(bucket) => truncate_to_bucket(date_time, bucket),
+        }
+    }
+
+    fn coordinate(&self, value: f64) -> f64 {
+        match self.policy.coordinate_grid {
+            Some(grid) if grid > 0.0 => round_to_grid(value, grid),
+            _ => value,
+        }
+    }
+
+    pub fn sanitize(&self, notice: &Notice) -> SanitizedNotice {
+        SanitizedNotice {
+            token: self.pseudonym("token", &notice.token),
+            status: notice.status.to_string(),
+            street: if self.policy.remove_house_number {
+                street_without_house_number(&notice.street)
+            } else {
+                notice.street.clone()
+            },
+            city: notice.city.clone(),
+            zip: notice.zip.clone(),
+            latitude: self.coordinate(notice.latitude),
+            longitude: self.coordinate(notice.longitude),
+            registration: self.registration(&notice.registration),
+            registration_prefix: self.registration_prefix(&notice.registration),
+            color: notice.color.clone(),
+            brand: notice.brand.clone(),
+            tbnr: notice.tbnr.clone(),
+            start_date: self.date_time(notice.start_date),
+            end_date: self.date_time(notice.end_date),
+            note: match (self.policy.note, &notice.note) {
+                (NotePolicy::Keep, note) => note.clone(),
+                (NotePolicy::Obfuscate, Some(note)) => {
+                    Some(obfuscate_note(note, &notice.registration))
+                }
+                _ => None,
+            },
+            created_at: self.date_time(notice.created_at),
+            sent_at: self.date_time(notice.sent_at),
+            vehicle_empty: notice.vehicle_empty,
+            hazard_lights: notice.hazard_lights,
+            expired_tuv: notice.expired_tuv,
+            expired_eco: notice.expired_eco,
+            over_2_8_tons: notice.over_2_8_tons,
+        }
+    }
+
+    pub fn sanitize_all(&self, notices: &[Notice]) -> Vec<SanitizedNotice> {
+        notices.iter().map(|notice| self.sanitize(notice)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::notice::{Notice, NoticeJson};
+
+    use super::{
+        AnonymizationPolicy, Anonymizer, NotePolicy, RegistrationPolicy, SanitizedNotice,
+        DEFAULT_ANONYMIZATION_POLICY,
+    };
+
+    const KEY: &[u8] = b"a secret key of at least 32 bytes";
+
+    fn notice(registration: &str, note: &str) -> Notice {
+        let json = format!(
+            r#"{{
+                "token": "abc123",
+                "status": "shared",
+                "street": "Hauptstraße 12a",
+                "city": "Hamburg",
+                "zip": "20095",
+                "latitude": 53.550712,
+                "longitude": 9.993284,
+                "registration": "{}",
+                "color": "silver",
+                "brand": "Opel",
+                "charge": {{
+                    "tbnr": "112454",
+                    "description": "Sie parkten verbotswidrig auf dem Gehweg.",
+                    "fine": "55.0",
+                    "bkat": "§ 12 Abs. 4, § 49 StVO; § 24 Abs. 1, 3 Nr. 5 StVG; 52a BKat",
+                    "penalty": null,
+                    "fap": null,
+                    "points": 0,
+                    "valid_from": "2021-11-09T00:00:00.000+01:00",
+                    "valid_to": null,
+                    "implementation": null,
+                    "classification": 5,
+                    "variant_table_id": 712031,
+                    "rule_id": 272,
+                    "table_id": null,
+                    "required_refinements": "00000000000000000000000000000000",
+                    "number_required_refinements": 0,
+                    "max_fine": "0.0",
+                    "created_at": "2023-09-18T15:30:27.417+02:00",
+                    "updated_at": "2023-09-18T15:30:27.417+02:00"
+                }},
+                "tbnr": "112454",
+                "start_date": "2023-10-25T09:23:00.000+01:00",
+                "end_date": "2023-10-25T09:41:00.000+01:00",
+                "note": "{}",
+                "photos": [],
+                "created_at": "2023-10-25T09:23:30.830+01:00",
+                "updated_at": "2023-10-25T09:41:42.638+01:00",
+                "sent_at": "2023-10-25T09:42:32.612+01:00",
+                "vehicle_empty": true,
+                "hazard_lights": false,
+                "expired_tuv": false,
+                "expired_eco": false,
+                "over_2_8_tons": false
+            }}"#,
+            registration, note
+        );
+        Notice::try_from(&serde_json::from_str::<NoticeJson>(&json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_anonymizer() {
+        let anonymizer = Anonymizer::new(KEY, DEFAULT_ANONYMIZATION_POLICY).unwrap();
+        let sanitized = anonymizer.sanitize(&notice("HH-AB 123", "Fahrer HH AB 123 angesprochen"));
+        let registration = sanitized.registration.clone().unwrap();
+        assert_eq!(sanitized.registration_prefix.as_deref(), Some("HH"));
+        assert!(!registration.contains("AB"));
+        assert_eq!(registration.len(), 32);
+        // spelling variants get the same pseudonym, also if they can't be parsed for the prefix
+        for variant in ["hh ab123", "HHAB123"] {
+            assert_eq!(
+                anonymizer
+                    .sanitize(&notice(variant, ""))
+                    .registration
+                    .unwrap(),
+                registration
+            );
+        }
+        assert_eq!(
+            anonymizer
+                .sanitize(&notice("HHAB123", ""))
+                .registration_prefix,
+            None
+        );
+        // equal values of different fields get unrelated pseudonyms
+        assert_ne!(
+            anonymizer.pseudonym("token", "HHAB123"),
+            anonymizer.pseudonym("registration", "HHAB123")
+        );
+        // other keys get another pseudonym
+        assert_ne!(
+            Anonymizer::new(
+                b"another secret key of 32 bytes..",
+                DEFAULT_ANONYMIZATION_POLICY
+            )
+            .unwrap()
+            .sanitize(&notice("HH AB 123", ""))
+            .registration
+            .unwrap(),
+            registration
+        );
+        assert_ne!(sanitized.token, "abc123");
+        assert_eq!(sanitized.street, "Hauptstraße");
+        assert_eq!(sanitized.note, None);
+        assert_eq!(sanitized.latitude, 53.551);
+        assert_eq!(sanitized.longitude, 9.993);
+        assert_eq!(
+            sanitized.start_date.to_rfc3339(),
+            "2023-10-25T09:15:00+01:00"
+        );
+        assert_eq!(sanitized.end_date.to_rfc3339(), "2023-10-25T09:30:00+01:00");
+
+        let json = serde_json::to_string(&sanitized).unwrap();
+        assert!(!json.contains("AB 123"));
+        assert_eq!(
+            serde_json::from_str::<SanitizedNotice>(&json).unwrap(),
+            sanitized
+        );
+
+        let anonymizer = Anonymizer::new(
+            KEY,
+            AnonymizationPolicy {
+                registration: RegistrationPolicy::Remove,
+                note: NotePolicy::Obfuscate,
+                coordinate_grid: None,
+                time_bucket: None,
+                remove_house_number: false,
+            },
+        )
+        .unwrap();
+        assert!(Anonymizer::new(b"secret", DEFAULT_ANONYMIZATION_POLICY).is_err());
+        let sanitized = anonymizer.sanitize(&notice(
+            "HH AB 123",
+            "Halter HH-AB123 (HH AB 123) ruft 0170123 an",
+        ));
+        assert_eq!(sanitized.registration, None);
+        assert_eq!(sanitized.registration_prefix, None);
+        assert_eq!(
+            sanitized.note.unwrap(),
+            "Halter *** *** *** *** ruft *** an"
+        );
+        assert_eq!(sanitized.latitude, 53.550712);
+        assert_eq!(sanitized.street, "Hauptstraße 12a");
+    }
+}
@@ -0,0 +1,128 @@
+use std::future::Future;
+
+use futures_util::{stream, StreamExt};
+
+use crate::types::request::BulkOptions;
+
+use super::error::ApiError;
+
+/// Run `fetch` for every key with at most `options.concurrency` requests in flight
+///
+/// Results are returned in the order of `keys`, each paired with its key. A failing item doesn't affect the others.
+pub async fn fetch_bulk<I, T, F, Fut>(
+    keys: I,
+    options: &BulkOptions,
+    fetch: F,
+) -> Vec<(String, Result<T, ApiError>)>
+where
+    I: IntoIterator<Item = String>,
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = (String, Result<T, ApiError>)>,
+{
+    stream::iter(keys)
+        .map(|key| {
+            let rate_limiter = options.rate_limiter.clone();
+            let request = fetch(key);
+            async move {
+                if let Some(rate_limiter) = rate_limiter {
+                    rate_limiter.acquire().await;
+                }
+                request.await
+            }
+        })
+        .buffered(options.concurrency.max(1))
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        api::{
+            error::ApiError,
+            rate_limit::{RateLimiter, DEFAULT_RATE_LIMIT_SETTINGS},
+            WegLiApiClient,
+        },
+        types::request::{BulkOptions, RateLimitSettings, RetrySettings},
+    };
+
+    #[tokio::test]
+    async fn test_get_charges_by_tbnr() {
+        let mut server = mockito::Server::new_async().await;
+        let found_mock = server
+            .mock("GET", "/charges/112454")
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"{
+                    "tbnr": "112454",
+                    "description": "Sie parkten verbotswidrig auf dem Gehweg.",
+                    "fine": "55.0",
+                    "bkat": "§ 12 Abs. 4, § 49 StVO; § 24 Abs. 1, 3 Nr. 5 StVG; 52a BKat",
+                    "penalty": null,
+                    "fap": null,
+                    "points": 0,
+                    "valid_from": "2021-11-09T00:00:00.000+01:00",
+                    "valid_to": null,
+                    "implementation": null,
+                    "classification": 5,
+                    "variant_table_id": 712031,
+                    "rule_id": 272,
+                    "table_id": null,
+                    "required_refinements": "00000000000000000000000000000000",
+                    "number_required_refinements": 0,
+                    "max_fine": "0.0",
+                    "created_at": "2023-09-18T15:30:27.417+02:00",
+                    "updated_at": "2023-09-18T15:30:27.417+02:00"
+                }"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        let missing_mock = server
+            .mock("GET", "/charges/999999")
+            .with_status(404)
+            .create_async()
+            .await;
+        let client = WegLiApiClient::new(
+            &server.url(),
+            &"any_api_key".to_string(),
+            Some(RetrySettings {
+                max_retries: 0,
+                initial_backoff_ms: 0,
+                backoff_multiplier: 1,
+            }),
+        )
+        .unwrap();
+
+        let results = client
+            .get_charges_by_tbnr(
+                ["112454", "999999", "112454"].map(String::from),
+                &BulkOptions {
+                    concurrency: 2,
+                    rate_limiter: Some(Arc::new(
+                        RateLimiter::new(RateLimitSettings {
+                            requests_per_second: 100.0,
+                            burst: 1,
+                            ..DEFAULT_RATE_LIMIT_SETTINGS
+                        })
+                        .unwrap(),
+                    )),
+                },
+            )
+            .await;
+        let keys: Vec<&str> = results.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["112454", "999999", "112454"]);
+        assert_eq!(results[0].1.as_ref().unwrap().tbnr, "112454");
+        assert!(matches!(
+            results[1].1,
+            Err(ApiError::UnexpectedStatusCode(status)) if status == 404
+        ));
+        assert!(results[2].1.is_ok());
+
+        found_mock.assert_async().await;
+        missing_mock.assert_async().await;
+    }
+}
@@ -2,32 +2,25 @@ use url::Url;
 
 use crate::types::{
     charge::{Charge, ChargeJson},
-    request::RetrySettings,
+    request::EndpointGroup,
 };
 
 use super::{
     error::ApiError,
-    request::{execute_request, RetryData, DEFAULT_RETRY_SETTINGS},
+    request::{execute_request, RequestPolicies},
 };
 
 pub async fn get_charge_from_wegli_api(
     api_url: &Url,
     api_token: &String,
     tbnr: &String,
-    retry_settings: &Option<RetrySettings>,
+    policies: &RequestPolicies,
 ) -> Result<Charge, ApiError> {
-    let retry_data = RetryData {
-        retry_count: 0,
-        settings: match retry_settings {
-            Some(settings) => settings.clone(),
-            None => DEFAULT_RETRY_SETTINGS,
-        },
-    };
     let request_builder = reqwest::Client::new()
         .get(format!("{}{}{}", api_url, "charges/", tbnr))
         .header("X-API-KEY", api_token);
 
-    let response = match execute_request(&request_builder, &Some(retry_data)).await {
+    let response = match execute_request(&request_builder, EndpointGroup::Charges, policies).await {
         Err(error) => return Err(error),
         Ok(response) => response,
     };
@@ -49,20 +42,13 @@ pub async fn get_charge_from_wegli_api(
 pub async fn get_charges_from_wegli_api(
     api_url: &Url,
     api_token: &String,
-    retry_settings: &Option<RetrySettings>,
+    policies: &RequestPolicies,
 ) -> Result<Vec<Charge>, ApiError> {
-    let retry_data = RetryData {
-        retry_count: 0,
-        settings: match retry_settings {
-            Some(settings) => settings.clone(),
-            None => DEFAULT_RETRY_SETTINGS,
-        },
-    };
     let request_builder = reqwest::Client::new()
         .get(format!("{}{}", api_url, "charges"))
         .header("X-API-KEY", api_token);
 
-    let response = match execute_request(&request_builder, &Some(retry_data)).await {
+    let response = match execute_request(&request_builder, EndpointGroup::Charges, policies).await {
         Err(error) => return Err(error),
         Ok(response) => response,
     };
@@ -94,6 +80,8 @@ mod tests {
 
     use url::Url;
 
+    use crate::api::request::RequestPolicies;
+
     use super::{get_charge_from_wegli_api, get_charges_from_wegli_api};
 
     #[tokio::test]
@@ -134,7 +122,7 @@ mod tests {
             &Url::from_str(&server.url()).unwrap(),
             &"any_api_key".to_string(),
             &"101000".to_string(),
-            &None,
+            &RequestPolicies::default(),
         )
         .await
         .unwrap();
@@ -202,7 +190,7 @@ mod tests {
         let response = get_charges_from_wegli_api(
             &Url::from_str(&server.url()).unwrap(),
             &"any_api_key".to_string(),
-            &None,
+            &RequestPolicies::default(),
         )
         .await
         .unwrap();
@@ -0,0 +1,318 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::types::request::{CircuitBreakerSettings, EndpointGroup};
+
+use super::error::ApiError;
+
+pub const DEFAULT_CIRCUIT_BREAKER_SETTINGS: CircuitBreakerSettings = CircuitBreakerSettings {
+    failure_threshold: 5,
+    cooldown: Duration::from_secs(60),
+    half_open_max_requests: 1,
+};
+
+/// State of the circuit of one [EndpointGroup]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent, counting consecutive failures
+    Closed { consecutive_failures: u32 },
+    /// Requests fail fast until the cooldown has passed
+    Open { until: Instant },
+    /// Trial requests are sent; one success closes the circuit, one failure opens it again
+    ///
+    /// If no trial reports back within the cooldown after `since`, new trial requests are let through.
+    HalfOpen { trial_requests: u32, since: Instant },
+}
+
+/// Stops sending requests to a group of endpoints after repeated failures
+///
+/// Connection errors and 5xx or 429 responses count as failures, other responses as successes.
+/// A response counts by the status the server answered with, even if a middleware fails on it afterwards.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    settings: CircuitBreakerSettings,
+    circuits: Mutex<HashMap<EndpointGroup, CircuitState>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(settings: CircuitBreakerSettings) -> Self {
+        CircuitBreaker {
+            settings,
+            circuits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn settings(&self) -> &CircuitBreakerSettings {
+        &self.settings
+    }
+
+    pub fn state(&self, group: EndpointGroup) -> CircuitState {
+        match self.circuits.lock().unwrap().get(&group) {
+            None => CircuitState::Closed {
+                consecutive_failures: 0,
+            },
+            Some(state) => *state,
+        }
+    }
+
+    /// Check whether an attempt may be sent, failing with [ApiError::CircuitOpen] if not
+    pub fn before_attempt(&self, group: EndpointGroup) -> Result<(), ApiError> {
+        let mut circuits = self.circuits.lock().unwrap();
+        let state = circuits.entry(group).or_insert(CircuitState::Closed {
+            consecutive_failures: 0,
+        });
+        match *state {
+            CircuitState::Closed { .. } => Ok(()),
+            CircuitState::Open { until } => {
+                let now = Instant::now();
+                if now < until {
+                    return Err(ApiError::CircuitOpen(group));
+                }
+                *state = CircuitState::HalfOpen {
+                    trial_requests: 1,
+                    since: now,
+                };
+                Ok(())
+            }
+            CircuitState::HalfOpen {
+                trial_requests,
+                since,
+            } => {
+                let now = Instant::now();
+                // trials that never reported back don't keep the circuit half open forever
+                if now >= since + self.settings.cooldown {
+                    *state = CircuitState::HalfOpen {
+                        trial_requests: 1,
+                        since: now,
+                    };
+                    return Ok(());
+                }
+                if trial_requests >= self.settings.half_open_max_requests {
+                    return Err(ApiError::CircuitOpen(group));
+                }
+                *state = CircuitState::HalfOpen {
+                    trial_requests: trial_requests + 1,
+                    since,
+                };
+                Ok(())
+            }
+        }
+    }
+
+    pub fn record_success(&self, group: EndpointGroup) {
+        self.circuits.lock().unwrap().insert(
+            group,
+            CircuitState::Closed {
+                consecutive_failures: 0,
+            },
+        );
+    }
+
+    /// Record an attempt that ended without a response from the API, e.g. because of a middleware error or
+    /// because the request was dropped. It counts as neither success nor failure, but frees its trial slot.
+    pub fn record_abandoned(&self, group: EndpointGroup) {
+        let mut circuits = self.circuits.lock().unwrap();
+        if let Some(CircuitState::HalfOpen {
+            trial_requests,
+            since,
+        }) = circuits.get(&group).copied()
+        {
+            circuits.insert(
+                group,
+                CircuitState::HalfOpen {
+                    trial_requests: trial_requests.saturating_sub(1),
+                    since,
+                },
+            );
+        }
+    }
+
+    pub fn record_failure(&self, group: EndpointGroup) {
+        let mut circuits = self.circuits.lock().unwrap();
+        let state = circuits.entry(group).or_insert(CircuitState::Closed {
+            consecutive_failures: 0,
+        });
+        let open = CircuitState::Open {
+            until: Instant::now() + self.settings.cooldown,
+        };
+        *state = match *state {
+            CircuitState::Closed {
+                consecutive_failures,
+            } => {
+                if consecutive_failures + 1 >= self.settings.failure_threshold {
+                    open
+                } else {
+                    CircuitState::Closed {
+                        consecutive_failures: consecutive_failures + 1,
+                    }
+                }
+            }
+            // a late failure of a request sent before the circuit opened doesn't extend the cooldown
+            CircuitState::Open { until } => CircuitState::Open { until },
+            CircuitState::HalfOpen { .. } => open,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use anyhow::anyhow;
+    use async_trait::async_trait;
+    use reqwest::Response;
+
+    use crate::{
+        api::{error::ApiError, middleware::Middleware, WegLiApiClient},
+        types::request::{CircuitBreakerSettings, EndpointGroup, RetrySettings},
+    };
+
+    use super::{CircuitBreaker, CircuitState, DEFAULT_CIRCUIT_BREAKER_SETTINGS};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker_states() {
+        let circuit_breaker = CircuitBreaker::new(CircuitBreakerSettings {
+            failure_threshold: 2,
+            cooldown: Duration::from_secs(10),
+            half_open_max_requests: 1,
+        });
+        let group = EndpointGroup::Charges;
+
+        circuit_breaker.record_failure(group);
+        assert!(circuit_breaker.before_attempt(group).is_ok());
+        circuit_breaker.record_failure(group);
+        assert!(matches!(
+            circuit_breaker.before_attempt(group),
+            Err(ApiError::CircuitOpen(EndpointGroup::Charges))
+        ));
+        // other groups are not affected
+        assert!(circuit_breaker
+            .before_attempt(EndpointGroup::Districts)
+            .is_ok());
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert!(circuit_breaker.before_attempt(group).is_ok());
+        assert!(matches!(
+            circuit_breaker.state(group),
+            CircuitState::HalfOpen {
+                trial_requests: 1,
+                ..
+            }
+        ));
+        assert!(circuit_breaker.before_attempt(group).is_err());
+        // an abandoned trial frees its slot
+        circuit_breaker.record_abandoned(group);
+        assert!(circuit_breaker.before_attempt(group).is_ok());
+        // a trial that never reports back is given up after the cooldown
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert!(circuit_breaker.before_attempt(group).is_err());
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert!(circuit_breaker.before_attempt(group).is_ok());
+        circuit_breaker.record_failure(group);
+        assert!(matches!(
+            circuit_breaker.state(group),
+            CircuitState::Open { .. }
+        ));
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert!(circuit_breaker.before_attempt(group).is_ok());
+        circuit_breaker.record_success(group);
+        assert_eq!(
+            circuit_breaker.state(group),
+            CircuitState::Closed {
+                consecutive_failures: 0
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_circuit_breaker_fails_fast() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/districts")
+            .with_status(500)
+            .expect(2)
+            .create_async()
+            .await;
+        let mut client = WegLiApiClient::new(
+            &server.url(),
+            &"any_api_key".to_string(),
+            Some(RetrySettings {
+                max_retries: 5,
+                initial_backoff_ms: 0,
+                backoff_multiplier: 1,
+            }),
+        )
+        .unwrap();
+        client.circuit_breaker = Some(Arc::new(CircuitBreaker::new(CircuitBreakerSettings {
+            failure_threshold: 2,
+            ..DEFAULT_CIRCUIT_BREAKER_SETTINGS
+        })));
+
+        assert!(matches!(
+            client.get_districts().await,
+            Err(ApiError::CircuitOpen(EndpointGroup::Districts))
+        ));
+        assert!(matches!(
+            client.get_districts().await,
+            Err(ApiError::CircuitOpen(EndpointGroup::Districts))
+        ));
+        mock.assert_async().await;
+    }
+
+    struct RejectResponse;
+
+    #[async_trait]
+    impl Middleware for RejectResponse {
+        async fn on_response(&self, _response: Response) -> Result<Response, anyhow::Error> {
+            Err(anyhow!("rejected"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_circuit_breaker_middleware_error() {
+        let mut server = mockito::Server::new_async().await;
+        let charges_mock = server
+            .mock("GET", "/charges")
+            .with_status(200)
+            .with_body("[]")
+            .create_async()
+            .await;
+        let districts_mock = server
+            .mock("GET", "/districts")
+            .with_status(500)
+            .create_async()
+            .await;
+        let mut client =
+            WegLiApiClient::new(&server.url(), &"any_api_key".to_string(), None).unwrap();
+        let circuit_breaker = Arc::new(CircuitBreaker::new(CircuitBreakerSettings {
+            failure_threshold: 1,
+            ..DEFAULT_CIRCUIT_BREAKER_SETTINGS
+        }));
+        client.circuit_breaker = Some(circuit_breaker.clone());
+        client.middleware.push(RejectResponse);
+
+        // the middleware error is returned, but the response the server sent still counts
+        assert!(matches!(
+            client.get_charges().await,
+            Err(ApiError::Middleware(_))
+        ));
+        assert_eq!(
+            circuit_breaker.state(EndpointGroup::Charges),
+            CircuitState::Closed {
+                consecutive_failures: 0
+            }
+        );
+        assert!(matches!(
+            client.get_districts().await,
+            Err(ApiError::Middleware(_))
+        ));
+        assert!(matches!(
+            circuit_breaker.state(EndpointGroup::Districts),
+            CircuitState::Open { .. }
+        ));
+        charges_mock.assert_async().await;
+        districts_mock.assert_async().await;
+    }
+}
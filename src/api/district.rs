@@ -2,32 +2,26 @@ use url::Url;
 
 use crate::types::{
     district::{District, DistrictJson},
-    request::RetrySettings,
+    request::EndpointGroup,
 };
 
 use super::{
     error::ApiError,
-    request::{execute_request, RetryData, DEFAULT_RETRY_SETTINGS},
+    request::{execute_request, RequestPolicies},
 };
 
 pub async fn get_district_from_wegli_api(
     api_url: &Url,
     api_token: &String,
     zip: &String,
-    retry_settings: &Option<RetrySettings>,
+    policies: &RequestPolicies,
 ) -> Result<District, ApiError> {
-    let retry_data = RetryData {
-        retry_count: 0,
-        settings: match retry_settings {
-            Some(settings) => settings.clone(),
-            None => DEFAULT_RETRY_SETTINGS,
-        },
-    };
     let request_builder = reqwest::Client::new()
         .get(format!("{}{}{}", api_url, "districts/", zip))
         .header("X-API-KEY", api_token);
 
-    let response = match execute_request(&request_builder, &Some(retry_data)).await {
+    let response = match execute_request(&request_builder, EndpointGroup::Districts, policies).await
+    {
         Err(error) => return Err(error),
         Ok(response) => response,
     };
@@ -49,20 +43,14 @@ pub async fn get_district_from_wegli_api(
 pub async fn get_districts_from_wegli_api(
     api_url: &Url,
     api_token: &String,
-    retry_settings: &Option<RetrySettings>,
+    policies: &RequestPolicies,
 ) -> Result<Vec<District>, ApiError> {
-    let retry_data = RetryData {
-        retry_count: 0,
-        settings: match retry_settings {
-            Some(settings) => settings.clone(),
-            None => DEFAULT_RETRY_SETTINGS,
-        },
-    };
     let request_builder = reqwest::Client::new()
         .get(format!("{}{}", api_url, "districts"))
         .header("X-API-KEY", api_token);
 
-    let response = match execute_request(&request_builder, &Some(retry_data)).await {
+    let response = match execute_request(&request_builder, EndpointGroup::Districts, policies).await
+    {
         Err(error) => return Err(error),
         Ok(response) => response,
     };
@@ -94,6 +82,8 @@ mod tests {
 
     use url::Url;
 
+    use crate::api::request::RequestPolicies;
+
     use super::{get_district_from_wegli_api, get_districts_from_wegli_api};
 
     #[tokio::test]
@@ -130,7 +120,7 @@ mod tests {
             &Url::from_str(&server.url()).unwrap(),
             &"any_api_key".to_string(),
             &"91443".to_string(),
-            &None,
+            &RequestPolicies::default(),
         )
         .await
         .unwrap();
@@ -187,7 +177,7 @@ mod tests {
         let response = get_districts_from_wegli_api(
             &Url::from_str(&server.url()).unwrap(),
             &"any_api_key".to_string(),
-            &None,
+            &RequestPolicies::default(),
         )
         .await
         .unwrap();
@@ -1,7 +1,9 @@
-use std::io;
+use std::{io, path::PathBuf};
 
 use thiserror::Error;
 
+use crate::types::request::EndpointGroup;
+
 #[derive(Error, Debug)]
 pub enum ApiError {
     #[error("API signals to wait (429 or 503)")]
@@ -19,6 +21,10 @@ pub enum ApiError {
     BackoffOverflow(String),
     #[error("could not clone RequestBuilder")]
     RequestBuilderClone(),
+    #[error("circuit for `{0}` endpoints is open after repeated failures")]
+    CircuitOpen(EndpointGroup),
+    #[error("middleware error: {0}")]
+    Middleware(anyhow::Error),
 }
 
 #[derive(Error, Debug)]
@@ -29,6 +35,19 @@ pub enum DownloadError {
     Io(io::Error),
     #[error("reqwest error")]
     Reqwest(reqwest::Error),
+    #[error("download was cancelled")]
+    Cancelled,
+    #[error("middleware error: {0}")]
+    Middleware(anyhow::Error),
+}
+
+/// Failure while sending a request through a [MiddlewareChain](super::middleware::MiddlewareChain)
+#[derive(Error, Debug)]
+pub enum MiddlewareError {
+    #[error("reqwest error")]
+    Reqwest(reqwest::Error),
+    #[error("middleware error: {0}")]
+    Middleware(anyhow::Error),
 }
 
 #[derive(Error, Debug)]
@@ -37,4 +56,18 @@ pub enum UnzipError {
     Io(io::Error),
     #[error("zip error")]
     Zip(zip::result::ZipError),
+    #[error("archive contains more than {0} entries")]
+    TooManyEntries(usize),
+    #[error("archive extracts to more than {0} bytes")]
+    TotalSizeExceeded(u64),
+    #[error("entry `{0}` exceeds the maximum compression ratio")]
+    CompressionRatioExceeded(String),
+    #[error("entry `{0}` has a file extension that is not allowed")]
+    ExtensionNotAllowed(String),
+    #[error("entry `{0}` would be extracted outside of the target directory")]
+    UnsafePath(String),
+    #[error("file `{0}` already exists")]
+    FileExists(PathBuf),
+    #[error("extraction was cancelled")]
+    Cancelled,
 }
@@ -4,32 +4,29 @@ use std::{
 };
 
 use anyhow::anyhow;
+use tokio_util::sync::CancellationToken;
 use url::Url;
 
 use crate::types::{
-    export::{Export, ExportJson},
-    request::RetrySettings,
+    download::DownloadOptions,
+    export::{Export, ExportJson, ExportSelector},
+    request::EndpointGroup,
+    unzip::UnzipLimits,
 };
 
 use super::{
-    error::ApiError,
-    request::{execute_request, RetryData, DEFAULT_RETRY_SETTINGS},
-    util::{download_to_dir, unzip_archive},
+    error::{ApiError, UnzipError},
+    middleware::MiddlewareChain,
+    request::{execute_request, RequestPolicies},
+    util::{download_to_dir, unzip_archive_cancellable, DEFAULT_UNZIP_LIMITS},
 };
 
 pub async fn get_exports_from_wegli_api(
     api_url: &Url,
     api_token: &String,
     public: bool,
-    retry_settings: &Option<RetrySettings>,
+    policies: &RequestPolicies,
 ) -> Result<Vec<Export>, ApiError> {
-    let retry_data = RetryData {
-        retry_count: 0,
-        settings: match retry_settings {
-            Some(settings) => settings.clone(),
-            None => DEFAULT_RETRY_SETTINGS,
-        },
-    };
     let request_builder = reqwest::Client::new()
         .get(format!(
             "{}{}{}",
@@ -39,7 +36,7 @@ pub async fn get_exports_from_wegli_api(
         ))
         .header("X-API-KEY", api_token);
 
-    let response = match execute_request(&request_builder, &Some(retry_data)).await {
+    let response = match execute_request(&request_builder, EndpointGroup::Exports, policies).await {
         Err(error) => return Err(error),
         Ok(response) => response,
     };
@@ -68,66 +65,106 @@ pub fn unzip_weg_li_notices_archive(
     zip_path: &Path,
     unzip_dir_path: &Path,
 ) -> Result<PathBuf, anyhow::Error> {
-    let csv_path = match unzip_archive(&zip_path, &unzip_dir_path) {
-        Err(error) => return Err(anyhow!(error)),
-        Ok(_) => {
-            let paths = match fs::read_dir(&unzip_dir_path) {
-                Err(error) => return Err(anyhow!(error)),
-                Ok(paths) => paths,
-            };
-            let mut found_csv: Option<PathBuf> = None;
-            for dir_entry in paths {
-                match dir_entry {
+    unzip_weg_li_notices_archive_with_limits(zip_path, unzip_dir_path, &DEFAULT_UNZIP_LIMITS)
+}
+
+/// Extract the export archive at `zip_path` into `unzip_dir_path` within `unzip_limits` and return the path of the CSV
+pub fn unzip_weg_li_notices_archive_with_limits(
+    zip_path: &Path,
+    unzip_dir_path: &Path,
+    unzip_limits: &UnzipLimits,
+) -> Result<PathBuf, anyhow::Error> {
+    unzip_notices_archive(zip_path, unzip_dir_path, unzip_limits, None)
+}
+
+/// Same as [unzip_weg_li_notices_archive_with_limits], but aborted once `cancellation_token` is cancelled
+///
+/// A cancelled extraction removes the archive at `zip_path`, the entries extracted so far are never moved into place.
+fn unzip_notices_archive(
+    zip_path: &Path,
+    unzip_dir_path: &Path,
+    unzip_limits: &UnzipLimits,
+    cancellation_token: Option<&CancellationToken>,
+) -> Result<PathBuf, anyhow::Error> {
+    let csv_path =
+        match unzip_archive_cancellable(zip_path, unzip_dir_path, unzip_limits, cancellation_token)
+        {
+            Err(UnzipError::Cancelled) => {
+                let _ = fs::remove_file(zip_path);
+                return Err(anyhow!(UnzipError::Cancelled));
+            }
+            Err(error) => return Err(anyhow!(error)),
+            Ok(_) => {
+                let paths = match fs::read_dir(&unzip_dir_path) {
                     Err(error) => return Err(anyhow!(error)),
-                    Ok(dir_entry) => {
-                        let file_name = match dir_entry.file_name().into_string() {
-                            Err(os_string) => {
-                                return Err(anyhow!("could not convert to string: {:?}", os_string))
+                    Ok(paths) => paths,
+                };
+                let mut found_csv: Option<PathBuf> = None;
+                for dir_entry in paths {
+                    match dir_entry {
+                        Err(error) => return Err(anyhow!(error)),
+                        Ok(dir_entry) => {
+                            let file_name = match dir_entry.file_name().into_string() {
+                                Err(os_string) => {
+                                    return Err(anyhow!(
+                                        "could not convert to string: {:?}",
+                                        os_string
+                                    ))
+                                }
+                                Ok(val) => val,
+                            };
+                            if file_name.to_lowercase().ends_with(".csv") {
+                                found_csv = Some(dir_entry.path())
                             }
-                            Ok(val) => val,
-                        };
-                        if file_name.to_lowercase().ends_with(".csv") {
-                            found_csv = Some(dir_entry.path())
                         }
                     }
                 }
+                match found_csv {
+                    Some(val) => val,
+                    None => return Err(anyhow!("could not find csv in: {:?}", &unzip_dir_path)),
+                }
             }
-            match found_csv {
-                Some(val) => val,
-                None => return Err(anyhow!("could not find csv in: {:?}", &unzip_dir_path)),
-            }
-        }
-    };
+        };
     return Ok(csv_path);
 }
 
-pub async fn download_latest_export_from_wegli(
+pub async fn get_selected_export_from_wegli_api(
     api_url: &Url,
     api_token: &String,
-    path: &Path,
     public: bool,
+    selector: &ExportSelector,
+    policies: &RequestPolicies,
+) -> Result<Export, anyhow::Error> {
+    match get_exports_from_wegli_api(api_url, api_token, public, policies).await {
+        Err(error) => Err(anyhow!(error)),
+        Ok(exports) => match selector.select(&exports) {
+            None => Err(anyhow!("no export found matching {:?}", selector)),
+            Some(export) => Ok(export.clone()),
+        },
+    }
+}
+
+pub async fn download_export_from_wegli(
+    export: &Export,
+    path: &Path,
     unzip: bool,
-    retry_settings: &Option<RetrySettings>,
+    unzip_limits: &Option<UnzipLimits>,
+    download_options: &DownloadOptions,
+    middleware: &MiddlewareChain,
 ) -> Result<PathBuf, anyhow::Error> {
-    let last_export =
-        match get_exports_from_wegli_api(api_url, api_token, public, retry_settings).await {
+    let download_path =
+        match download_to_dir(path, &export.download.url, download_options, middleware).await {
             Err(error) => return Err(anyhow!(error)),
-            Ok(mut exports) => {
-                exports.sort_by(|a, b| b.created_at.cmp(&a.created_at));
-                match exports.first().cloned() {
-                    None => return Err(anyhow!("no export found")),
-                    Some(export) => export,
-                }
-            }
+            Ok(val) => val,
         };
 
-    let download_path = match download_to_dir(&path, &last_export.download.url).await {
-        Err(error) => return Err(anyhow!(error)),
-        Ok(val) => val,
-    };
-
     if unzip {
-        return unzip_weg_li_notices_archive(&download_path, &path);
+        return unzip_notices_archive(
+            &download_path,
+            path,
+            unzip_limits.as_ref().unwrap_or(&DEFAULT_UNZIP_LIMITS),
+            download_options.cancellation_token.as_ref(),
+        );
     }
 
     Ok(download_path)
@@ -136,11 +173,18 @@ pub async fn download_latest_export_from_wegli(
 #[cfg(test)]
 mod tests {
 
-    use std::str::FromStr;
+    use std::{fs, io::Write, str::FromStr};
 
+    use tokio_util::sync::CancellationToken;
     use url::Url;
 
-    use super::get_exports_from_wegli_api;
+    use crate::types::export::ExportSelector;
+
+    use crate::api::{error::UnzipError, request::RequestPolicies, util::DEFAULT_UNZIP_LIMITS};
+
+    use super::{
+        get_exports_from_wegli_api, get_selected_export_from_wegli_api, unzip_notices_archive,
+    };
 
     #[tokio::test]
     async fn test_get_exports_from_wegli_api() {
@@ -181,7 +225,7 @@ mod tests {
             &Url::from_str(&server.url()).unwrap(),
             &"any_api_key".to_string(),
             true,
-            &None,
+            &RequestPolicies::default(),
         )
         .await
         .unwrap();
@@ -191,4 +235,97 @@ mod tests {
         );
         mock.assert();
     }
+
+    #[tokio::test]
+    async fn test_get_selected_export_from_wegli_api() {
+        let mut server = mockito::Server::new_async().await;
+
+        let mock = server
+            .mock("GET", "/exports/public")
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(
+                r#"
+                [
+                    {
+                        "export_type": "notices",
+                        "file_extension": "csv",
+                        "created_at": "2022-11-14T03:01:58.056+01:00",
+                        "download": {
+                            "filename": "notices-46.zip",
+                            "url": "https://www.weg.li/rails/active_storage/blobs/redirect/.../notices-46.zip"
+                        }
+                    },
+                    {
+                        "export_type": "notices",
+                        "file_extension": "csv",
+                        "created_at": "2022-11-21T03:02:19.396+01:00",
+                        "download": {
+                            "filename": "notices-47.zip",
+                            "url": "https://www.weg.li/rails/active_storage/blobs/redirect/.../notices-47.zip"
+                        }
+                    }
+                ]
+                "#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+
+        let export = get_selected_export_from_wegli_api(
+            &Url::from_str(&server.url()).unwrap(),
+            &"any_api_key".to_string(),
+            true,
+            &ExportSelector::LatestBefore(
+                chrono::DateTime::parse_from_rfc3339("2022-11-20T00:00:00+01:00").unwrap(),
+            ),
+            &RequestPolicies::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(&export.download.filename, &"notices-46.zip".to_string());
+
+        let export = get_selected_export_from_wegli_api(
+            &Url::from_str(&server.url()).unwrap(),
+            &"any_api_key".to_string(),
+            true,
+            &ExportSelector::Latest,
+            &RequestPolicies::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(&export.download.filename, &"notices-47.zip".to_string());
+        mock.assert();
+    }
+
+    #[test]
+    fn test_unzip_notices_archive_cancelled() {
+        let dir = std::env::temp_dir().join("weg_li_api_test_unzip_notices_archive_cancelled");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let zip_path = dir.join("notices-47.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        zip.start_file("notices-47.csv", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"token\nabc123\n").unwrap();
+        zip.finish().unwrap();
+
+        let cancellation_token = CancellationToken::new();
+        cancellation_token.cancel();
+        let error = unzip_notices_archive(
+            &zip_path,
+            &dir,
+            &DEFAULT_UNZIP_LIMITS,
+            Some(&cancellation_token),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<UnzipError>(),
+            Some(UnzipError::Cancelled)
+        ));
+        // neither the archive nor any extracted entry is left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
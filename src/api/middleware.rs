@@ -0,0 +1,218 @@
+use std::{fmt, sync::Arc};
+
+use async_trait::async_trait;
+use reqwest::{Request, Response, StatusCode};
+
+use super::error::MiddlewareError;
+
+/// Hook to inspect or modify requests sent and responses received by [WegLiApiClient](super::WegLiApiClient)
+///
+/// Applies to API calls, every retry included, as well as file downloads. Returning an error aborts the request.
+#[async_trait]
+pub trait Middleware: Send + Sync {
+    /// Called before a request is sent, e.g. to add headers or rewrite the URL
+    async fn on_request(&self, _request: &mut Request) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    /// Called with every response received. The returned response is passed on, so it can be replaced.
+    async fn on_response(&self, response: Response) -> Result<Response, anyhow::Error> {
+        Ok(response)
+    }
+}
+
+/// Ordered list of [Middleware]
+///
+/// Requests pass the middleware in the order they were added, responses in reverse order,
+/// so the first middleware added sees the request first and the response last, wrapping all middleware added after it.
+#[derive(Clone, Default)]
+pub struct MiddlewareChain {
+    middleware: Vec<Arc<dyn Middleware>>,
+}
+
+impl MiddlewareChain {
+    pub fn push<M: Middleware + 'static>(&mut self, middleware: M) {
+        self.middleware.push(Arc::new(middleware));
+    }
+
+    pub fn len(&self) -> usize {
+        self.middleware.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.middleware.is_empty()
+    }
+
+    /// Pass `request` through all middleware and send it with `client`
+    pub async fn execute(
+        &self,
+        client: &reqwest::Client,
+        request: Request,
+    ) -> Result<Response, MiddlewareError> {
+        self.execute_with_status(client, request).await.1
+    }
+
+    /// Same as [execute](MiddlewareChain::execute), but also returns the status the server answered with, if it did
+    ///
+    /// The status is the one received, before any middleware could replace the response or fail.
+    pub(crate) async fn execute_with_status(
+        &self,
+        client: &reqwest::Client,
+        mut request: Request,
+    ) -> (Option<StatusCode>, Result<Response, MiddlewareError>) {
+        for middleware in self.middleware.iter() {
+            if let Err(error) = middleware.on_request(&mut request).await {
+                return (None, Err(MiddlewareError::Middleware(error)));
+            }
+        }
+        let mut response = match client.execute(request).await {
+            Err(error) => return (None, Err(MiddlewareError::Reqwest(error))),
+            Ok(val) => val,
+        };
+        let status = response.status();
+        for middleware in self.middleware.iter().rev() {
+            response = match middleware.on_response(response).await {
+                Err(error) => return (Some(status), Err(MiddlewareError::Middleware(error))),
+                Ok(val) => val,
+            };
+        }
+        (Some(status), Ok(response))
+    }
+}
+
+impl fmt::Debug for MiddlewareChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MiddlewareChain({} middleware)", self.middleware.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::anyhow;
+    use async_trait::async_trait;
+    use reqwest::{Request, Response};
+
+    use crate::{
+        api::{error::ApiError, WegLiApiClient},
+        types::{download::DownloadOptions, notice::NoticePhotosJson},
+    };
+
+    use super::Middleware;
+
+    struct CorrelationId {
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Middleware for CorrelationId {
+        async fn on_request(&self, request: &mut Request) -> Result<(), anyhow::Error> {
+            request
+                .headers_mut()
+                .insert("X-Correlation-Id", "42".parse().unwrap());
+            self.log
+                .lock()
+                .unwrap()
+                .push("correlation request".to_string());
+            Ok(())
+        }
+
+        async fn on_response(&self, response: Response) -> Result<Response, anyhow::Error> {
+            self.log.lock().unwrap().push(format!(
+                "correlation response {}",
+                response.status().as_u16()
+            ));
+            Ok(response)
+        }
+    }
+
+    /// Sends requests for another host to the test server instead, or fails them if `fail` is set
+    struct Proxy {
+        url: String,
+        fail: bool,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Middleware for Proxy {
+        async fn on_request(&self, request: &mut Request) -> Result<(), anyhow::Error> {
+            if self.fail {
+                return Err(anyhow!("injected fault"));
+            }
+            let url = format!("{}{}", self.url, request.url().path());
+            *request.url_mut() = url.parse().unwrap();
+            self.log.lock().unwrap().push("proxy request".to_string());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware_chain() {
+        let mut server = mockito::Server::new_async().await;
+        let api_mock = server
+            .mock("GET", "/charges")
+            .match_header("X-Correlation-Id", "42")
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body("[]")
+            .create_async()
+            .await;
+        let photo_mock = server
+            .mock("GET", "/photos/photo.jpg")
+            .match_header("X-Correlation-Id", "42")
+            .with_status(200)
+            .with_body("jpg")
+            .create_async()
+            .await;
+
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut client =
+            WegLiApiClient::new(&server.url(), &"any_api_key".to_string(), None).unwrap();
+        client.middleware.push(CorrelationId { log: log.clone() });
+        client.middleware.push(Proxy {
+            url: server.url(),
+            fail: false,
+            log: log.clone(),
+        });
+
+        assert!(client.get_charges().await.unwrap().is_empty());
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "correlation request",
+                "proxy request",
+                "correlation response 200"
+            ]
+        );
+
+        let dir = std::env::temp_dir().join("weg_li_api_test_middleware_chain");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let photo = NoticePhotosJson {
+            filename: "photo.jpg".to_string(),
+            url: "https://photos.example.com/photos/photo.jpg".to_string(),
+        };
+        let photo_path = client
+            .download_notice_photo(&photo, &dir, &DownloadOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&photo_path).unwrap(), b"jpg");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut failing_client =
+            WegLiApiClient::new(&server.url(), &"any_api_key".to_string(), None).unwrap();
+        failing_client.middleware.push(Proxy {
+            url: server.url(),
+            fail: true,
+            log,
+        });
+        assert!(matches!(
+            failing_client.get_charges().await,
+            Err(ApiError::Middleware(_))
+        ));
+
+        api_mock.assert_async().await;
+        photo_mock.assert_async().await;
+    }
+}
@@ -1,34 +1,67 @@
+mod bulk;
 mod charge;
+pub mod circuit_breaker;
 mod district;
 pub mod error;
 pub mod export;
+pub mod middleware;
 mod notice;
+pub mod rate_limit;
 pub mod request;
 pub mod util;
+pub mod wegli_api;
 
 use std::{
     path::{Path, PathBuf},
     str::FromStr,
+    sync::Arc,
 };
 
 use anyhow::anyhow;
+use bulk::fetch_bulk;
 use charge::{get_charge_from_wegli_api, get_charges_from_wegli_api};
+use circuit_breaker::CircuitBreaker;
 use district::{get_district_from_wegli_api, get_districts_from_wegli_api};
-use error::ApiError;
-use export::{download_latest_export_from_wegli, get_exports_from_wegli_api};
-use notice::{get_notice_from_wegli_api, get_notices_from_wegli_api};
+use error::{ApiError, DownloadError};
+use export::{
+    download_export_from_wegli, get_exports_from_wegli_api, get_selected_export_from_wegli_api,
+};
+use middleware::MiddlewareChain;
+use notice::{
+    download_notice_photo_from_wegli, get_notice_from_wegli_api, get_notices_from_wegli_api,
+};
+use rate_limit::RateLimiter;
+use request::RequestPolicies;
 use url::Url;
 
 use crate::types::{
-    charge::Charge, district::District, export::Export, notice::Notice, request::RetrySettings,
+    charge::Charge,
+    district::District,
+    download::DownloadOptions,
+    export::{Export, ExportSelector},
+    notice::{Notice, NoticePhotosJson},
+    request::{BulkOptions, RetrySettings},
+    unzip::UnzipLimits,
 };
 
+/// Client for the weg.li API
+///
+/// Clones share the client's `rate_limiter` and `circuit_breaker`, so parallel tasks stay below a common rate and stop together during outages.
+#[derive(Clone)]
 pub struct WegLiApiClient {
     api_url: Url,
     api_token: String,
     /// Retry settings for exponential backoff are activated by default (initial_backoff_ms: 300, max_retries: 5, backoff_multiplier: 2).
     /// If you do not want to retry, provide a retry_settings argument with max_retries set to 0.
     pub retry_settings: Option<RetrySettings>,
+    /// Limits applied when extracting downloaded export archives. Defaults to [DEFAULT_UNZIP_LIMITS](util::DEFAULT_UNZIP_LIMITS) if `None`.
+    pub unzip_limits: Option<UnzipLimits>,
+    /// Limits the rate of API requests, adapting to 429 and 503 responses. No limit is applied if `None`, e.g. use [DEFAULT_RATE_LIMIT_SETTINGS](rate_limit::DEFAULT_RATE_LIMIT_SETTINGS).
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Fails requests fast while weg.li is failing repeatedly, per group of endpoints. Disabled if `None`, e.g. use [DEFAULT_CIRCUIT_BREAKER_SETTINGS](circuit_breaker::DEFAULT_CIRCUIT_BREAKER_SETTINGS).
+    pub circuit_breaker: Option<Arc<CircuitBreaker>>,
+    /// Middleware applied to all API requests and downloads, see [Middleware](middleware::Middleware)
+    pub middleware: MiddlewareChain,
 }
 
 impl WegLiApiClient {
@@ -44,22 +77,55 @@ impl WegLiApiClient {
             },
             api_token: api_token.to_string(),
             retry_settings,
+            unzip_limits: None,
+            rate_limiter: None,
+            circuit_breaker: None,
+            middleware: MiddlewareChain::default(),
         })
     }
+    fn request_policies(&self) -> RequestPolicies {
+        RequestPolicies {
+            retry_settings: self.retry_settings.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            circuit_breaker: self.circuit_breaker.clone(),
+            middleware: self.middleware.clone(),
+        }
+    }
     /// Get a single notice of the authenticated user by its token
     pub async fn get_notice(&self, notice_token: &String) -> Result<Notice, ApiError> {
         return get_notice_from_wegli_api(
             &self.api_url,
             &self.api_token,
             notice_token,
-            &self.retry_settings,
+            &self.request_policies(),
         )
         .await;
     }
     /// Get all notices of the authenticated user
     pub async fn get_notices(&self) -> Result<Vec<Notice>, ApiError> {
-        return get_notices_from_wegli_api(&self.api_url, &self.api_token, &self.retry_settings)
-            .await;
+        return get_notices_from_wegli_api(
+            &self.api_url,
+            &self.api_token,
+            &self.request_policies(),
+        )
+        .await;
+    }
+    /// Get notices of the authenticated user by their tokens, see [BulkOptions] for concurrency and rate limiting
+    ///
+    /// Returns one result per token in the order given, a failing token doesn't fail the others.
+    pub async fn get_notices_by_token<I>(
+        &self,
+        notice_tokens: I,
+        options: &BulkOptions,
+    ) -> Vec<(String, Result<Notice, ApiError>)>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        fetch_bulk(notice_tokens, options, |notice_token| async move {
+            let result = self.get_notice(&notice_token).await;
+            (notice_token, result)
+        })
+        .await
     }
     /// Get a single charge by its tbnr
     pub async fn get_charge(&self, tbnr: &String) -> Result<Charge, ApiError> {
@@ -67,14 +133,33 @@ impl WegLiApiClient {
             &self.api_url,
             &self.api_token,
             tbnr,
-            &self.retry_settings,
+            &self.request_policies(),
         )
         .await;
     }
     /// Get all charges
     pub async fn get_charges(&self) -> Result<Vec<Charge>, ApiError> {
-        return get_charges_from_wegli_api(&self.api_url, &self.api_token, &self.retry_settings)
-            .await;
+        return get_charges_from_wegli_api(
+            &self.api_url,
+            &self.api_token,
+            &self.request_policies(),
+        )
+        .await;
+    }
+    /// Get charges by their tbnrs, see [get_notices_by_token](WegLiApiClient::get_notices_by_token)
+    pub async fn get_charges_by_tbnr<I>(
+        &self,
+        tbnrs: I,
+        options: &BulkOptions,
+    ) -> Vec<(String, Result<Charge, ApiError>)>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        fetch_bulk(tbnrs, options, |tbnr| async move {
+            let result = self.get_charge(&tbnr).await;
+            (tbnr, result)
+        })
+        .await
     }
     /// Get a single district by zip code
     pub async fn get_district(&self, zip: &String) -> Result<District, ApiError> {
@@ -82,14 +167,33 @@ impl WegLiApiClient {
             &self.api_url,
             &self.api_token,
             zip,
-            &self.retry_settings,
+            &self.request_policies(),
         )
         .await;
     }
     /// Get all districts
     pub async fn get_districts(&self) -> Result<Vec<District>, ApiError> {
-        return get_districts_from_wegli_api(&self.api_url, &self.api_token, &self.retry_settings)
-            .await;
+        return get_districts_from_wegli_api(
+            &self.api_url,
+            &self.api_token,
+            &self.request_policies(),
+        )
+        .await;
+    }
+    /// Get districts by their zip codes, see [get_notices_by_token](WegLiApiClient::get_notices_by_token)
+    pub async fn get_districts_by_zip<I>(
+        &self,
+        zips: I,
+        options: &BulkOptions,
+    ) -> Vec<(String, Result<District, ApiError>)>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        fetch_bulk(zips, options, |zip| async move {
+            let result = self.get_district(&zip).await;
+            (zip, result)
+        })
+        .await
     }
     /// Get metadata of exports of the currently authenticated user
     pub async fn get_user_exports(&self) -> Result<Vec<Export>, ApiError> {
@@ -97,7 +201,7 @@ impl WegLiApiClient {
             &self.api_url,
             &self.api_token,
             false,
-            &self.retry_settings,
+            &self.request_policies(),
         )
         .await;
     }
@@ -107,7 +211,7 @@ impl WegLiApiClient {
             &self.api_url,
             &self.api_token,
             true,
-            &self.retry_settings,
+            &self.request_policies(),
         )
         .await;
     }
@@ -118,20 +222,93 @@ impl WegLiApiClient {
     /// `public` gets the publicly available export if set to `true`, otherwise the authenticated user's ones.
     ///
     /// Returns the path to the zip file if `unzip` is `false``, otherwise the path to the first (and as of current weg.li behavior only) .csv file extracted.
+    ///
+    /// Extraction is bounded by the client's `unzip_limits`.
     pub async fn download_latest_export(
         &self,
         path: &Path,
         public: bool,
         unzip: bool,
     ) -> Result<PathBuf, anyhow::Error> {
-        return download_latest_export_from_wegli(
+        return self
+            .download_latest_export_with_options(path, public, unzip, &DownloadOptions::default())
+            .await;
+    }
+    /// Same as [download_latest_export](WegLiApiClient::download_latest_export), with `download_options` to report the download progress and cancel the download, see [DownloadOptions]
+    pub async fn download_latest_export_with_options(
+        &self,
+        path: &Path,
+        public: bool,
+        unzip: bool,
+        download_options: &DownloadOptions,
+    ) -> Result<PathBuf, anyhow::Error> {
+        return self
+            .download_selected_export(
+                &ExportSelector::Latest,
+                path,
+                public,
+                unzip,
+                download_options,
+            )
+            .await;
+    }
+    /// Download the notice export archive picked by `selector`, e.g. the one a previous analysis was run on
+    ///
+    /// Arguments and return value are the same as for [download_latest_export](WegLiApiClient::download_latest_export).
+    pub async fn download_selected_export(
+        &self,
+        selector: &ExportSelector,
+        path: &Path,
+        public: bool,
+        unzip: bool,
+        download_options: &DownloadOptions,
+    ) -> Result<PathBuf, anyhow::Error> {
+        let export = match get_selected_export_from_wegli_api(
             &self.api_url,
             &self.api_token,
-            path,
             public,
+            selector,
+            &self.request_policies(),
+        )
+        .await
+        {
+            Err(error) => return Err(error),
+            Ok(val) => val,
+        };
+        return self
+            .download_export(&export, path, unzip, download_options)
+            .await;
+    }
+    /// Download the archive of an export as listed by [get_public_exports](WegLiApiClient::get_public_exports) or [get_user_exports](WegLiApiClient::get_user_exports)
+    ///
+    /// Returns the path to the zip file if `unzip` is `false`, otherwise the path to the .csv file extracted.
+    pub async fn download_export(
+        &self,
+        export: &Export,
+        path: &Path,
+        unzip: bool,
+        download_options: &DownloadOptions,
+    ) -> Result<PathBuf, anyhow::Error> {
+        return download_export_from_wegli(
+            export,
+            path,
             unzip,
-            &self.retry_settings,
+            &self.unzip_limits,
+            download_options,
+            &self.middleware,
         )
         .await;
     }
+    /// Download a photo attached to a notice into the directory `path`
+    ///
+    /// Returns the path of the downloaded photo.
+    pub async fn download_notice_photo(
+        &self,
+        photo: &NoticePhotosJson,
+        path: &Path,
+        download_options: &DownloadOptions,
+    ) -> Result<PathBuf, DownloadError> {
+        return download_notice_photo_from_wegli(photo, path, download_options, &self.middleware)
+            .await;
+    }
 }
@@ -1,33 +1,31 @@
+use std::path::{Path, PathBuf};
+
 use url::Url;
 
 use crate::types::{
-    notice::{Notice, NoticeJson},
-    request::RetrySettings,
+    download::DownloadOptions,
+    notice::{Notice, NoticeJson, NoticePhotosJson},
+    request::EndpointGroup,
 };
 
 use super::{
-    error::ApiError,
-    request::{execute_request, RetryData, DEFAULT_RETRY_SETTINGS},
+    error::{ApiError, DownloadError},
+    middleware::MiddlewareChain,
+    request::{execute_request, RequestPolicies},
+    util::download_to_dir,
 };
 
 pub async fn get_notice_from_wegli_api(
     api_url: &Url,
     api_token: &String,
     notice_token: &String,
-    retry_settings: &Option<RetrySettings>,
+    policies: &RequestPolicies,
 ) -> Result<Notice, ApiError> {
-    let retry_data = RetryData {
-        retry_count: 0,
-        settings: match retry_settings {
-            Some(settings) => settings.clone(),
-            None => DEFAULT_RETRY_SETTINGS,
-        },
-    };
     let request_builder = reqwest::Client::new()
         .get(format!("{}{}{}", api_url, "notices/", notice_token))
         .header("X-API-KEY", api_token);
 
-    let response = match execute_request(&request_builder, &Some(retry_data)).await {
+    let response = match execute_request(&request_builder, EndpointGroup::Notices, policies).await {
         Err(error) => return Err(error),
         Ok(response) => response,
     };
@@ -49,20 +47,13 @@ pub async fn get_notice_from_wegli_api(
 pub async fn get_notices_from_wegli_api(
     api_url: &Url,
     api_token: &String,
-    retry_settings: &Option<RetrySettings>,
+    policies: &RequestPolicies,
 ) -> Result<Vec<Notice>, ApiError> {
-    let retry_data = RetryData {
-        retry_count: 0,
-        settings: match retry_settings {
-            Some(settings) => settings.clone(),
-            None => DEFAULT_RETRY_SETTINGS,
-        },
-    };
     let request_builder = reqwest::Client::new()
         .get(format!("{}{}", api_url, "notices"))
         .header("X-API-KEY", api_token);
 
-    let response = match execute_request(&request_builder, &Some(retry_data)).await {
+    let response = match execute_request(&request_builder, EndpointGroup::Notices, policies).await {
         Err(error) => return Err(error),
         Ok(response) => response,
     };
@@ -87,12 +78,23 @@ pub async fn get_notices_from_wegli_api(
     };
 }
 
+pub async fn download_notice_photo_from_wegli(
+    photo: &NoticePhotosJson,
+    path: &Path,
+    download_options: &DownloadOptions,
+    middleware: &MiddlewareChain,
+) -> Result<PathBuf, DownloadError> {
+    download_to_dir(path, &photo.url, download_options, middleware).await
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
     use url::Url;
 
+    use crate::api::request::RequestPolicies;
+
     use super::get_notice_from_wegli_api;
 
     #[tokio::test]
@@ -162,7 +164,7 @@ mod tests {
             &Url::from_str(&server.url()).unwrap(),
             &"any_api_key".to_string(),
             &"abc123".to_string(),
-            &None,
+            &RequestPolicies::default(),
         )
         .await
         .unwrap();
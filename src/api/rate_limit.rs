@@ -0,0 +1,286 @@
+use std::{sync::Mutex, time::Duration};
+
+use anyhow::anyhow;
+use tokio::time::Instant;
+
+use crate::types::request::{RateLimitSettings, RateLimiterStats};
+
+pub const DEFAULT_RATE_LIMIT_SETTINGS: RateLimitSettings = RateLimitSettings {
+    requests_per_second: 5.0,
+    burst: 5,
+    min_requests_per_second: 0.2,
+    decrease_factor: 0.5,
+    recovery_step: 0.05,
+};
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+    /// Current rate, lowered on throttling and recovering towards the configured rate
+    requests_per_second: f64,
+    paused_until: Option<Instant>,
+    requests: u64,
+    throttled: u64,
+    total_wait: Duration,
+}
+
+/// Token bucket limiting the rate of requests sent, shared by reference between tasks
+///
+/// The bucket holds up to `burst` tokens and is refilled at the current rate. Every request takes one token and waits if there is none left.
+///
+/// The rate adapts to the API: it is multiplied by `decrease_factor` whenever the API throttles,
+/// and grows by `recovery_step` with every successful request until it is back at `requests_per_second`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    settings: RateLimitSettings,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    /// Create a limiter, failing if a rate is not positive and finite or the factors are out of range
+    pub fn new(settings: RateLimitSettings) -> Result<Self, anyhow::Error> {
+        if !(settings.requests_per_second > 0.0 && settings.requests_per_second.is_finite()) {
+            return Err(anyhow!(
+                "requests_per_second must be positive: {}",
+                settings.requests_per_second
+            ));
+        }
+        if !(settings.min_requests_per_second > 0.0 && settings.min_requests_per_second.is_finite())
+        {
+            return Err(anyhow!(
+                "min_requests_per_second must be positive: {}",
+                settings.min_requests_per_second
+            ));
+        }
+        if !(settings.decrease_factor > 0.0 && settings.decrease_factor <= 1.0) {
+            return Err(anyhow!(
+                "decrease_factor must be between 0 and 1: {}",
+                settings.decrease_factor
+            ));
+        }
+        if !(settings.recovery_step >= 0.0 && settings.recovery_step.is_finite()) {
+            return Err(anyhow!(
+                "recovery_step must not be negative: {}",
+                settings.recovery_step
+            ));
+        }
+        Ok(RateLimiter {
+            bucket: Mutex::new(Bucket {
+                tokens: settings.burst as f64,
+                last_refill: Instant::now(),
+                requests_per_second: settings.requests_per_second,
+                paused_until: None,
+                requests: 0,
+                throttled: 0,
+                total_wait: Duration::ZERO,
+            }),
+            settings,
+        })
+    }
+
+    pub fn settings(&self) -> &RateLimitSettings {
+        &self.settings
+    }
+
+    pub fn stats(&self) -> RateLimiterStats {
+        let bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        RateLimiterStats {
+            current_requests_per_second: bucket.requests_per_second,
+            requests: bucket.requests,
+            throttled: bucket.throttled,
+            total_wait: bucket.total_wait,
+            paused_for: match bucket.paused_until {
+                Some(paused_until) if paused_until > now => Some(paused_until - now),
+                _ => None,
+            },
+        }
+    }
+
+    /// Take a token if one is available, otherwise return how long to wait for the next one
+    fn try_acquire(&self) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        if let Some(paused_until) = bucket.paused_until {
+            if paused_until > now {
+                return Err(paused_until - now);
+            }
+            bucket.paused_until = None;
+        }
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * bucket.requests_per_second)
+            .min(self.settings.burst.max(1) as f64);
+        bucket.last_refill = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            bucket.requests += 1;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64(
+            (1.0 - bucket.tokens) / bucket.requests_per_second,
+        ))
+    }
+
+    /// Wait until a request may be sent
+    pub async fn acquire(&self) {
+        let start = Instant::now();
+        while let Err(wait) = self.try_acquire() {
+            tokio::time::sleep(wait).await;
+        }
+        self.bucket.lock().unwrap().total_wait += start.elapsed();
+    }
+
+    /// Slow down after the API signalled to wait, pausing all requests for `retry_after` if given
+    pub fn throttle(&self, retry_after: Option<Duration>) {
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        bucket.throttled += 1;
+        bucket.tokens = 0.0;
+        bucket.last_refill = now;
+        bucket.requests_per_second = (bucket.requests_per_second * self.settings.decrease_factor)
+            .max(self.settings.min_requests_per_second);
+        if let Some(retry_after) = retry_after {
+            let paused_until = now + retry_after;
+            bucket.paused_until = Some(match bucket.paused_until {
+                Some(previous) if previous > paused_until => previous,
+                _ => paused_until,
+            });
+            // no tokens accumulate while paused
+            bucket.last_refill = paused_until;
+        }
+    }
+
+    /// Raise the rate a step towards the configured rate after a successful request
+    pub fn recover(&self) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.requests_per_second = (bucket.requests_per_second + self.settings.recovery_step)
+            .min(self.settings.requests_per_second);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use tokio::time::Instant;
+
+    use crate::{
+        api::WegLiApiClient,
+        types::request::{RateLimitSettings, RetrySettings},
+    };
+
+    use super::{RateLimiter, DEFAULT_RATE_LIMIT_SETTINGS};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter_acquire() {
+        let rate_limiter = RateLimiter::new(RateLimitSettings {
+            requests_per_second: 10.0,
+            burst: 2,
+            ..DEFAULT_RATE_LIMIT_SETTINGS
+        })
+        .unwrap();
+        let start = Instant::now();
+        for _ in 0..2 {
+            rate_limiter.acquire().await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(1));
+        for _ in 0..5 {
+            rate_limiter.acquire().await;
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(500));
+        assert!(elapsed < Duration::from_millis(600));
+
+        rate_limiter.throttle(Some(Duration::from_secs(2)));
+        let stats = rate_limiter.stats();
+        assert_eq!(stats.current_requests_per_second, 5.0);
+        assert_eq!(stats.throttled, 1);
+        assert_eq!(stats.paused_for, Some(Duration::from_secs(2)));
+        let start = Instant::now();
+        rate_limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(2200));
+        rate_limiter.recover();
+        assert!((rate_limiter.stats().current_requests_per_second - 5.05).abs() < 1e-9);
+        assert_eq!(rate_limiter.stats().requests, 8);
+
+        assert!(RateLimiter::new(RateLimitSettings {
+            min_requests_per_second: 0.0,
+            ..DEFAULT_RATE_LIMIT_SETTINGS
+        })
+        .is_err());
+        assert!(RateLimiter::new(RateLimitSettings {
+            requests_per_second: f64::NAN,
+            ..DEFAULT_RATE_LIMIT_SETTINGS
+        })
+        .is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter_refill_after_pause() {
+        let rate_limiter = RateLimiter::new(RateLimitSettings {
+            requests_per_second: 10.0,
+            burst: 5,
+            ..DEFAULT_RATE_LIMIT_SETTINGS
+        })
+        .unwrap();
+        // the refill starts at the end of the pause, which lies in the future
+        rate_limiter.throttle(Some(Duration::from_secs(2)));
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(rate_limiter.try_acquire(), Err(Duration::from_secs(1)));
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(rate_limiter.try_acquire(), Err(Duration::from_millis(200)));
+        // refilled at the lowered rate of 5 per second since the pause ended
+        tokio::time::advance(Duration::from_secs(1)).await;
+        for _ in 0..5 {
+            assert_eq!(rate_limiter.try_acquire(), Ok(()));
+        }
+        assert!(rate_limiter.try_acquire().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_client_rate_limiter_on_429() {
+        let mut server = mockito::Server::new_async().await;
+        // mocks are used in order of creation until they have their expected hits
+        let throttled_mock = server
+            .mock("GET", "/charges")
+            .with_status(429)
+            .with_header("Retry-After", "1")
+            .expect(1)
+            .create_async()
+            .await;
+        let ok_mock = server
+            .mock("GET", "/charges")
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body("[]")
+            .expect(1)
+            .create_async()
+            .await;
+        let mut client = WegLiApiClient::new(
+            &server.url(),
+            &"any_api_key".to_string(),
+            Some(RetrySettings {
+                max_retries: 1,
+                initial_backoff_ms: 0,
+                backoff_multiplier: 1,
+            }),
+        )
+        .unwrap();
+        client.rate_limiter = Some(Arc::new(
+            RateLimiter::new(DEFAULT_RATE_LIMIT_SETTINGS).unwrap(),
+        ));
+        let cloned_client = client.clone();
+
+        let start = Instant::now();
+        assert!(cloned_client.get_charges().await.unwrap().is_empty());
+        assert!(start.elapsed() >= Duration::from_secs(1));
+        // the clone shares the limiter with the original client
+        let stats = client.rate_limiter.as_ref().unwrap().stats();
+        assert_eq!(stats.throttled, 1);
+        assert_eq!(stats.requests, 2);
+        assert!((stats.current_requests_per_second - 2.55).abs() < 1e-9);
+        throttled_mock.assert_async().await;
+        ok_mock.assert_async().await;
+    }
+}
@@ -1,9 +1,15 @@
-use reqwest::Response;
+use std::{sync::Arc, time::Duration};
 
-use crate::types::request::RetrySettings;
+use reqwest::{Response, StatusCode};
 
-use super::error::ApiError;
-use std::{thread, time};
+use crate::types::request::{EndpointGroup, RetrySettings};
+
+use super::{
+    circuit_breaker::CircuitBreaker,
+    error::{ApiError, MiddlewareError},
+    middleware::MiddlewareChain,
+    rate_limit::RateLimiter,
+};
 
 #[derive(Debug, Clone)]
 pub struct RetryData {
@@ -17,6 +23,23 @@ pub const DEFAULT_RETRY_SETTINGS: RetrySettings = RetrySettings {
     backoff_multiplier: 2,
 };
 
+/// Policies applied to every request sent by [execute_request]
+#[derive(Debug, Clone, Default)]
+pub struct RequestPolicies {
+    /// Settings for exponential backoff on unexpected status codes. Defaults to [DEFAULT_RETRY_SETTINGS] if `None`.
+    pub retry_settings: Option<RetrySettings>,
+    /// Limiter every attempt waits for. It is slowed down when the API responds with 429 or 503.
+    ///
+    /// This also decides how a 429 or 503 is handled: with a limiter, the request is retried within `retry_settings`
+    /// once the limiter allows it. Without one, the request fails immediately with [ApiError::ApiRequestsWait]
+    /// and waiting for the returned `Retry-After` is left to the caller.
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+    /// Fails fast with [ApiError::CircuitOpen] while the circuit of the request's endpoint group is open. Every failed attempt counts.
+    pub circuit_breaker: Option<Arc<CircuitBreaker>>,
+    /// Middleware every attempt is passed through
+    pub middleware: MiddlewareChain,
+}
+
 fn get_retry_after_header(response: &Response) -> Option<u64> {
     match response
         .headers()
@@ -34,9 +57,103 @@ fn get_retry_after_header(response: &Response) -> Option<u64> {
     }
 }
 
+/// Returns the retry data for the next attempt and the backoff to wait before it, or `None` if no retries are left
+fn next_retry(retry_data: &RetryData) -> Result<Option<(RetryData, Duration)>, ApiError> {
+    if retry_data.retry_count == retry_data.settings.max_retries
+        || retry_data.settings.max_retries == 0
+    {
+        return Ok(None);
+    }
+    let mut next_retry_data = retry_data.clone();
+    next_retry_data.retry_count += 1;
+    let backoff_ms = match next_retry_data
+        .settings
+        .backoff_multiplier
+        .checked_pow(next_retry_data.retry_count)
+    {
+        None => {
+            return Err(ApiError::BackoffOverflow(
+                "exceeded maximum backoff value".to_string(),
+            ))
+        }
+        Some(exp) => next_retry_data.settings.initial_backoff_ms * exp,
+    };
+    Ok(Some((next_retry_data, Duration::from_millis(backoff_ms))))
+}
+
+/// Permit of the circuit breaker for a single attempt
+///
+/// Dropping it without recording success or failure, e.g. when a middleware fails before the request is sent or the request future is dropped,
+/// records the attempt as abandoned, so a half open circuit doesn't wait for a trial that never reports back.
+struct CircuitAttempt<'a> {
+    circuit_breaker: Option<&'a CircuitBreaker>,
+    group: EndpointGroup,
+}
+
+impl<'a> CircuitAttempt<'a> {
+    fn start(
+        circuit_breaker: Option<&'a CircuitBreaker>,
+        group: EndpointGroup,
+    ) -> Result<Self, ApiError> {
+        if let Some(circuit_breaker) = circuit_breaker {
+            circuit_breaker.before_attempt(group)?;
+        }
+        Ok(CircuitAttempt {
+            circuit_breaker,
+            group,
+        })
+    }
+
+    fn success(mut self) {
+        if let Some(circuit_breaker) = self.circuit_breaker.take() {
+            circuit_breaker.record_success(self.group);
+        }
+    }
+
+    fn failure(mut self) {
+        if let Some(circuit_breaker) = self.circuit_breaker.take() {
+            circuit_breaker.record_failure(self.group);
+        }
+    }
+
+    /// Record the outcome of the response the server answered with
+    fn finish(self, status: StatusCode) {
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            self.failure();
+        } else {
+            self.success();
+        }
+    }
+}
+
+impl Drop for CircuitAttempt<'_> {
+    fn drop(&mut self) {
+        if let Some(circuit_breaker) = self.circuit_breaker.take() {
+            circuit_breaker.record_abandoned(self.group);
+        }
+    }
+}
+
 pub async fn execute_request(
     request_builder: &reqwest::RequestBuilder,
-    retry_data: &Option<RetryData>,
+    endpoint_group: EndpointGroup,
+    policies: &RequestPolicies,
+) -> Result<reqwest::Response, ApiError> {
+    let retry_data = RetryData {
+        retry_count: 0,
+        settings: match &policies.retry_settings {
+            Some(settings) => settings.clone(),
+            None => DEFAULT_RETRY_SETTINGS,
+        },
+    };
+    execute_request_attempt(request_builder, endpoint_group, policies, &retry_data).await
+}
+
+async fn execute_request_attempt(
+    request_builder: &reqwest::RequestBuilder,
+    endpoint_group: EndpointGroup,
+    policies: &RequestPolicies,
+    retry_data: &RetryData,
 ) -> Result<reqwest::Response, ApiError> {
     let local_request_builder = match request_builder.try_clone() {
         None => {
@@ -45,51 +162,77 @@ pub async fn execute_request(
         }
         Some(val) => val,
     };
-    let response = match local_request_builder.send().await {
+    let (client, request) = local_request_builder.build_split();
+    let request = match request {
         Err(error) => return Err(ApiError::Reqwest(error)),
         Ok(val) => val,
     };
+    if let Some(rate_limiter) = &policies.rate_limiter {
+        rate_limiter.acquire().await;
+    }
+    // checked after waiting for the rate limiter, so a trial slot isn't held while waiting
+    let circuit_attempt =
+        CircuitAttempt::start(policies.circuit_breaker.as_deref(), endpoint_group)?;
+    let (received_status, response) = policies
+        .middleware
+        .execute_with_status(&client, request)
+        .await;
+    // the circuit breaker judges the server by the status it answered with, even if a middleware failed afterwards
+    match received_status {
+        None => {
+            if let Err(MiddlewareError::Reqwest(_)) = &response {
+                circuit_attempt.failure();
+            }
+        }
+        Some(status) => circuit_attempt.finish(status),
+    }
+    let response = match response {
+        Err(MiddlewareError::Reqwest(error)) => return Err(ApiError::Reqwest(error)),
+        Err(MiddlewareError::Middleware(error)) => return Err(ApiError::Middleware(error)),
+        Ok(val) => val,
+    };
 
     if !response.status().is_success() {
         if [429, 503].contains(&response.status().as_u16()) {
-            match get_retry_after_header(&response) {
-                None => return Err(ApiError::ApiRequestsWait(None)),
-                Some(retry_after_value) => {
-                    return Err(ApiError::ApiRequestsWait(Some(retry_after_value)))
-                }
+            let retry_after = get_retry_after_header(&response);
+            let rate_limiter = match &policies.rate_limiter {
+                // without a rate limiter, waiting is left to the caller
+                None => return Err(ApiError::ApiRequestsWait(retry_after)),
+                Some(val) => val,
+            };
+            rate_limiter.throttle(retry_after.map(Duration::from_secs));
+            let (next_retry_data, backoff) = match next_retry(retry_data)? {
+                None => return Err(ApiError::ApiRequestsWait(retry_after)),
+                Some(val) => val,
+            };
+            // the rate limiter holds back the next attempt until Retry-After has passed
+            if retry_after.is_none() {
+                tokio::time::sleep(backoff).await;
             }
+            return Box::pin(execute_request_attempt(
+                request_builder,
+                endpoint_group,
+                policies,
+                &next_retry_data,
+            ))
+            .await;
         }
-        match retry_data {
+        let (next_retry_data, backoff) = match next_retry(retry_data)? {
             None => return Err(ApiError::UnexpectedStatusCode(response.status())),
-            Some(retry_data) => {
-                let mut iter_retry_data = retry_data.clone();
-                if iter_retry_data.retry_count == iter_retry_data.settings.max_retries
-                    || iter_retry_data.settings.max_retries == 0
-                {
-                    return Err(ApiError::UnexpectedStatusCode(response.status()));
-                }
-                iter_retry_data.retry_count += 1;
-
-                thread::sleep(time::Duration::from_millis(
-                    iter_retry_data.settings.initial_backoff_ms
-                        * match iter_retry_data
-                            .settings
-                            .backoff_multiplier
-                            .checked_pow(iter_retry_data.retry_count)
-                        {
-                            None => {
-                                return Err(ApiError::BackoffOverflow(
-                                    "exceeded maximum backoff value".to_string(),
-                                ))
-                            }
-                            Some(exp) => exp,
-                        },
-                ));
-
-                return Box::pin(execute_request(request_builder, &Some(iter_retry_data))).await;
-            }
-        }
+            Some(val) => val,
+        };
+        tokio::time::sleep(backoff).await;
+        return Box::pin(execute_request_attempt(
+            request_builder,
+            endpoint_group,
+            policies,
+            &next_retry_data,
+        ))
+        .await;
     }
 
+    if let Some(rate_limiter) = &policies.rate_limiter {
+        rate_limiter.recover();
+    }
     Ok(response)
 }
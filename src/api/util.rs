@@ -1,14 +1,98 @@
 use futures_util::StreamExt;
 use std::{
     fs::{self, File},
-    io,
+    io::{self, Read},
     path::{Path, PathBuf},
 };
+use tokio::io::AsyncWriteExt;
+use tokio_util::sync::CancellationToken;
 use url::Url;
 
-use super::error::{DownloadError, UnzipError};
+use crate::types::{
+    download::{DownloadOptions, DownloadProgress},
+    unzip::{OverwritePolicy, UnzipLimits},
+};
+
+use super::{
+    error::{DownloadError, MiddlewareError, UnzipError},
+    middleware::MiddlewareChain,
+};
+
+async fn write_download_to_file(
+    url: Url,
+    file: &mut tokio::fs::File,
+    options: &DownloadOptions,
+    middleware: &MiddlewareChain,
+) -> Result<(), DownloadError> {
+    if let Some(token) = &options.cancellation_token {
+        if token.is_cancelled() {
+            return Err(DownloadError::Cancelled);
+        }
+    }
+    let client = reqwest::Client::new();
+    let request = match client.get(url).build() {
+        Err(error) => return Err(DownloadError::Reqwest(error)),
+        Ok(val) => val,
+    };
+    let response = match middleware.execute(&client, request).await {
+        Err(MiddlewareError::Reqwest(error)) => return Err(DownloadError::Reqwest(error)),
+        Err(MiddlewareError::Middleware(error)) => return Err(DownloadError::Middleware(error)),
+        Ok(val) => val,
+    };
+
+    let mut progress = DownloadProgress {
+        bytes_received: 0,
+        total_bytes: response.content_length(),
+    };
+    if let Some(reporter) = &options.progress {
+        reporter.report(progress);
+    }
+
+    let mut byte_stream = response.bytes_stream();
+
+    loop {
+        let item = match &options.cancellation_token {
+            None => byte_stream.next().await,
+            Some(token) => tokio::select! {
+                biased;
+                _ = token.cancelled() => return Err(DownloadError::Cancelled),
+                item = byte_stream.next() => item,
+            },
+        };
+        let reader = match item {
+            None => break,
+            Some(Ok(val)) => val,
+            Some(Err(error)) => return Err(DownloadError::Reqwest(error)),
+        };
+
+        if let Err(error) = tokio::io::copy(&mut reader.as_ref(), file).await {
+            return Err(DownloadError::Io(error));
+        }
+
+        progress.bytes_received += reader.len() as u64;
+        if let Some(reporter) = &options.progress {
+            reporter.report(progress);
+        }
+    }
+
+    match file.flush().await {
+        Err(error) => Err(DownloadError::Io(error)),
+        Ok(_) => Ok(()),
+    }
+}
 
-pub async fn download_to_dir(path: &Path, url: &String) -> Result<PathBuf, DownloadError> {
+/// Download the file at `url` into the directory `path`, named after the last segment of the URL
+///
+/// The download is written to a temporary file next to it and only renamed into place once complete.
+/// If the download fails or is cancelled through `options`, the temporary file is removed and an existing file of the same name is left untouched.
+///
+/// The request and response are passed through `middleware`.
+pub async fn download_to_dir(
+    path: &Path,
+    url: &String,
+    options: &DownloadOptions,
+    middleware: &MiddlewareChain,
+) -> Result<PathBuf, DownloadError> {
     let url = match Url::parse(&url) {
         Err(error) => return Err(DownloadError::UrlParse(error)),
         Ok(val) => val,
@@ -20,34 +104,114 @@ pub async fn download_to_dir(path: &Path, url: &String) -> Result<PathBuf, Downl
             Some(val) => val,
         },
     );
-    let mut tmp_file = match File::create(&fpath) {
+    let mut tmp_file_name = fpath.file_name().unwrap_or_default().to_os_string();
+    tmp_file_name.push(".download-tmp");
+    let tmp_path = fpath.with_file_name(tmp_file_name);
+    let mut tmp_file = match File::create(&tmp_path) {
         Err(error) => return Err(DownloadError::Io(error)),
         Ok(val) => tokio::fs::File::from(val),
     };
 
-    let response = match reqwest::get(url).await {
-        Err(error) => return Err(DownloadError::Reqwest(error)),
-        Ok(val) => val,
-    };
+    if let Err(error) = write_download_to_file(url, &mut tmp_file, options, middleware).await {
+        drop(tmp_file);
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(error);
+    }
+    drop(tmp_file);
+    if let Err(error) = tokio::fs::rename(&tmp_path, &fpath).await {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(DownloadError::Io(error));
+    }
 
-    let mut byte_stream = response.bytes_stream();
+    return Ok(fpath);
+}
 
-    while let Some(item) = byte_stream.next().await {
-        let reader = match item {
-            Ok(val) => val,
-            Err(error) => return Err(DownloadError::Reqwest(error)),
-        };
+pub const DEFAULT_UNZIP_LIMITS: UnzipLimits = UnzipLimits {
+    max_total_bytes: 8 * 1024 * 1024 * 1024,
+    max_entries: 16,
+    max_compression_ratio: 100,
+    allowed_extensions: None,
+    overwrite_policy: OverwritePolicy::Fail,
+};
 
-        match tokio::io::copy(&mut reader.as_ref(), &mut tmp_file).await {
-            Err(error) => return Err(DownloadError::Io(error)),
-            Ok(_) => (),
-        };
+fn has_allowed_extension(path: &Path, allowed_extensions: &Option<Vec<String>>) -> bool {
+    match allowed_extensions {
+        None => true,
+        Some(allowed_extensions) => match path.extension().and_then(|val| val.to_str()) {
+            None => false,
+            Some(extension) => allowed_extensions.iter().any(|allowed| {
+                allowed
+                    .trim_start_matches('.')
+                    .eq_ignore_ascii_case(extension)
+            }),
+        },
     }
-
-    return Ok(fpath);
 }
 
+/// Extract all entries of the zip archive at `zip_path` into `unzip_dir_path`, bounded by [DEFAULT_UNZIP_LIMITS]
 pub fn unzip_archive(zip_path: &Path, unzip_dir_path: &Path) -> Result<(), UnzipError> {
+    unzip_archive_with_limits(zip_path, unzip_dir_path, &DEFAULT_UNZIP_LIMITS)
+}
+
+/// Extract all entries of the zip archive at `zip_path` into `unzip_dir_path`
+///
+/// Extraction is aborted with a typed [UnzipError] as soon as one of the `limits` is exceeded.
+/// Sizes are checked against the values declared in the archive as well as the bytes actually extracted.
+/// Entries are extracted to temporary files first and only moved into place once the whole archive was extracted,
+/// so an aborted extraction leaves files that existed before untouched.
+pub fn unzip_archive_with_limits(
+    zip_path: &Path,
+    unzip_dir_path: &Path,
+    limits: &UnzipLimits,
+) -> Result<(), UnzipError> {
+    unzip_archive_cancellable(zip_path, unzip_dir_path, limits, None)
+}
+
+/// Same as [unzip_archive_with_limits], but aborted with [UnzipError::Cancelled] before the next entry once `cancellation_token` is cancelled
+pub(crate) fn unzip_archive_cancellable(
+    zip_path: &Path,
+    unzip_dir_path: &Path,
+    limits: &UnzipLimits,
+    cancellation_token: Option<&CancellationToken>,
+) -> Result<(), UnzipError> {
+    let mut staged_paths: Vec<(PathBuf, PathBuf)> = vec![];
+    let mut result = extract_archive_entries(
+        zip_path,
+        unzip_dir_path,
+        limits,
+        cancellation_token,
+        &mut staged_paths,
+    );
+    if result.is_ok() {
+        for (tmp_path, outpath) in &staged_paths {
+            if let Err(error) = fs::rename(tmp_path, outpath) {
+                result = Err(UnzipError::Io(error));
+                break;
+            }
+        }
+    }
+    if result.is_err() {
+        for (tmp_path, _) in staged_paths {
+            let _ = fs::remove_file(tmp_path);
+        }
+    }
+    result
+}
+
+/// Temporary path an entry is extracted to before it is moved to `outpath`
+fn staging_path(outpath: &Path) -> PathBuf {
+    let mut file_name = outpath.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".unzip-tmp");
+    outpath.with_file_name(file_name)
+}
+
+fn extract_archive_entries(
+    zip_path: &Path,
+    unzip_dir_path: &Path,
+    limits: &UnzipLimits,
+    cancellation_token: Option<&CancellationToken>,
+    staged_paths: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<(), UnzipError> {
     let zipfile = match File::open(zip_path) {
         Err(error) => return Err(UnzipError::Io(error)),
         Ok(file) => file,
@@ -56,39 +220,261 @@ pub fn unzip_archive(zip_path: &Path, unzip_dir_path: &Path) -> Result<(), Unzip
         Err(error) => return Err(UnzipError::Zip(error)),
         Ok(val) => val,
     };
+    if archive.len() > limits.max_entries {
+        return Err(UnzipError::TooManyEntries(limits.max_entries));
+    }
+    let mut total_bytes: u64 = 0;
     for i in 0..archive.len() {
-        let mut file = match archive.by_index(i) {
+        if cancellation_token.is_some_and(|token| token.is_cancelled()) {
+            return Err(UnzipError::Cancelled);
+        }
+        let file = match archive.by_index(i) {
             Err(error) => return Err(UnzipError::Zip(error)),
             Ok(val) => val,
         };
         let outpath = match file.enclosed_name() {
-            Some(path) => std::path::Path::new(unzip_dir_path).join(path),
-            None => continue,
+            Some(path) if !file.is_symlink() => std::path::Path::new(unzip_dir_path).join(path),
+            _ => return Err(UnzipError::UnsafePath(file.name().to_string())),
         };
         if file.is_dir() {
-            match fs::create_dir_all(&outpath) {
-                Err(error) => return Err(UnzipError::Io(error)),
-                Ok(_) => {}
-            };
-        } else {
-            if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    match fs::create_dir_all(p) {
-                        Err(error) => return Err(UnzipError::Io(error)),
-                        Ok(_) => {}
-                    };
+            if let Err(error) = fs::create_dir_all(&outpath) {
+                return Err(UnzipError::Io(error));
+            }
+            continue;
+        }
+        if !has_allowed_extension(&outpath, &limits.allowed_extensions) {
+            return Err(UnzipError::ExtensionNotAllowed(file.name().to_string()));
+        }
+        let remaining_bytes = limits.max_total_bytes.saturating_sub(total_bytes);
+        if file.size() > remaining_bytes {
+            return Err(UnzipError::TotalSizeExceeded(limits.max_total_bytes));
+        }
+        let ratio_bytes = file
+            .compressed_size()
+            .saturating_mul(limits.max_compression_ratio);
+        if file.size() > ratio_bytes {
+            return Err(UnzipError::CompressionRatioExceeded(
+                file.name().to_string(),
+            ));
+        }
+        let staged = staged_paths.iter().any(|(_, path)| path == &outpath);
+        if outpath.exists() || staged {
+            match limits.overwrite_policy {
+                OverwritePolicy::Overwrite => {}
+                OverwritePolicy::Skip => continue,
+                OverwritePolicy::Fail => return Err(UnzipError::FileExists(outpath)),
+            }
+        }
+        if let Some(p) = outpath.parent() {
+            if !p.exists() {
+                if let Err(error) = fs::create_dir_all(p) {
+                    return Err(UnzipError::Io(error));
                 }
             }
-            let mut outfile = match fs::File::create(&outpath) {
-                Err(error) => return Err(UnzipError::Io(error)),
-                Ok(val) => val,
-            };
-            match io::copy(&mut file, &mut outfile) {
-                Err(error) => return Err(UnzipError::Io(error)),
-                Ok(_) => {}
-            };
         }
+        let tmp_path = staging_path(&outpath);
+        let mut outfile = match fs::File::create(&tmp_path) {
+            Err(error) => return Err(UnzipError::Io(error)),
+            Ok(val) => val,
+        };
+        if !staged {
+            staged_paths.push((tmp_path, outpath));
+        }
+        // the declared size may be forged, so never extract more than the limits allow
+        let entry_limit = remaining_bytes.min(ratio_bytes);
+        let entry_name = file.name().to_string();
+        let written = match io::copy(&mut file.take(entry_limit + 1), &mut outfile) {
+            Err(error) => return Err(UnzipError::Io(error)),
+            Ok(val) => val,
+        };
+        if written > entry_limit {
+            if ratio_bytes < remaining_bytes {
+                return Err(UnzipError::CompressionRatioExceeded(entry_name));
+            }
+            return Err(UnzipError::TotalSizeExceeded(limits.max_total_bytes));
+        }
+        total_bytes += written;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::Write,
+        path::{Path, PathBuf},
+    };
+
+    use std::sync::{Arc, Mutex};
+
+    use tokio_util::sync::CancellationToken;
+
+    use crate::{
+        api::{
+            error::{DownloadError, UnzipError},
+            middleware::MiddlewareChain,
+        },
+        types::{
+            download::{DownloadOptions, DownloadProgress, ProgressReporter},
+            unzip::{OverwritePolicy, UnzipLimits},
+        },
+    };
+
+    use super::{
+        download_to_dir, unzip_archive, unzip_archive_cancellable, unzip_archive_with_limits,
+        DEFAULT_UNZIP_LIMITS,
+    };
+
+    fn create_archive(dir: &Path, entries: &[(&str, &[u8])]) -> PathBuf {
+        let zip_path = dir.join("notices.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        for (name, content) in entries {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap();
+        zip_path
+    }
+
+    #[test]
+    fn test_unzip_archive_limits() {
+        let dir = std::env::temp_dir().join("weg_li_api_test_unzip_archive_limits");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let csv: String = (0..1000)
+            .map(|i: u64| format!("{:x}\n", i.wrapping_mul(0x9E3779B97F4A7C15)))
+            .collect();
+        let zip_path = create_archive(
+            &dir,
+            &[("notices.csv", csv.as_bytes()), ("run.sh", b"echo")],
+        );
+        let unzip_dir = dir.join("out");
+
+        let limits = UnzipLimits {
+            max_entries: 1,
+            ..DEFAULT_UNZIP_LIMITS
+        };
+        assert!(matches!(
+            unzip_archive_with_limits(&zip_path, &unzip_dir, &limits),
+            Err(UnzipError::TooManyEntries(1))
+        ));
+
+        let limits = UnzipLimits {
+            allowed_extensions: Some(vec!["csv".to_string()]),
+            ..DEFAULT_UNZIP_LIMITS
+        };
+        assert!(matches!(
+            unzip_archive_with_limits(&zip_path, &unzip_dir, &limits),
+            Err(UnzipError::ExtensionNotAllowed(name)) if name == "run.sh"
+        ));
+        // the entry extracted before the limit tripped is not moved into place
+        assert!(!unzip_dir.join("notices.csv").exists());
+
+        let limits = UnzipLimits {
+            max_compression_ratio: 1,
+            ..DEFAULT_UNZIP_LIMITS
+        };
+        assert!(matches!(
+            unzip_archive_with_limits(&zip_path, &unzip_dir, &limits),
+            Err(UnzipError::CompressionRatioExceeded(name)) if name == "notices.csv"
+        ));
+
+        let cancellation_token = CancellationToken::new();
+        cancellation_token.cancel();
+        assert!(matches!(
+            unzip_archive_cancellable(
+                &zip_path,
+                &unzip_dir,
+                &DEFAULT_UNZIP_LIMITS,
+                Some(&cancellation_token)
+            ),
+            Err(UnzipError::Cancelled)
+        ));
+
+        unzip_archive(&zip_path, &unzip_dir).unwrap();
+        assert!(matches!(
+            unzip_archive(&zip_path, &unzip_dir),
+            Err(UnzipError::FileExists(_))
+        ));
+        let limits = UnzipLimits {
+            overwrite_policy: OverwritePolicy::Overwrite,
+            ..DEFAULT_UNZIP_LIMITS
+        };
+        unzip_archive_with_limits(&zip_path, &unzip_dir, &limits).unwrap();
+
+        // files that existed before survive an aborted extraction, even when they would be overwritten
+        fs::write(unzip_dir.join("notices.csv"), "existing").unwrap();
+        let limits = UnzipLimits {
+            allowed_extensions: Some(vec!["csv".to_string()]),
+            overwrite_policy: OverwritePolicy::Overwrite,
+            ..DEFAULT_UNZIP_LIMITS
+        };
+        assert!(matches!(
+            unzip_archive_with_limits(&zip_path, &unzip_dir, &limits),
+            Err(UnzipError::ExtensionNotAllowed(_))
+        ));
+        assert_eq!(
+            fs::read_to_string(unzip_dir.join("notices.csv")).unwrap(),
+            "existing"
+        );
+        assert_eq!(fs::read_dir(&unzip_dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_download_to_dir_progress_and_cancellation() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/blobs/notices-47.zip")
+            .with_status(200)
+            .with_body(vec![0u8; 4096])
+            .create_async()
+            .await;
+        let dir = std::env::temp_dir().join("weg_li_api_test_download_to_dir");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let url = format!("{}/blobs/notices-47.zip", server.url());
+
+        let reported: Arc<Mutex<Vec<DownloadProgress>>> = Arc::new(Mutex::new(vec![]));
+        let reported_clone = reported.clone();
+        let options = DownloadOptions {
+            progress: Some(ProgressReporter::Callback(Arc::new(move |progress| {
+                reported_clone.lock().unwrap().push(progress)
+            }))),
+            cancellation_token: None,
+        };
+        let fpath = download_to_dir(&dir, &url, &options, &MiddlewareChain::default())
+            .await
+            .unwrap();
+        assert_eq!(fs::metadata(&fpath).unwrap().len(), 4096);
+        assert_eq!(
+            reported.lock().unwrap().last(),
+            Some(&DownloadProgress {
+                bytes_received: 4096,
+                total_bytes: Some(4096)
+            })
+        );
+        fs::write(&fpath, "previous download").unwrap();
+
+        let cancellation_token = CancellationToken::new();
+        cancellation_token.cancel();
+        let options = DownloadOptions {
+            progress: None,
+            cancellation_token: Some(cancellation_token),
+        };
+        assert!(matches!(
+            download_to_dir(&dir, &url, &options, &MiddlewareChain::default()).await,
+            Err(DownloadError::Cancelled)
+        ));
+        // the file downloaded before is kept and the partial download removed
+        assert_eq!(fs::read_to_string(&fpath).unwrap(), "previous download");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        mock.assert();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
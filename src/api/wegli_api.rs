@@ -0,0 +1,198 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+
+use crate::types::{
+    charge::Charge,
+    district::District,
+    download::DownloadOptions,
+    export::{Export, ExportSelector},
+    notice::{Notice, NoticePhotosJson},
+    request::BulkOptions,
+};
+
+use super::{
+    bulk::fetch_bulk,
+    error::{ApiError, DownloadError},
+    WegLiApiClient,
+};
+
+/// The operations of the weg.li API, implemented by [WegLiApiClient] and by [InMemoryWegLi](crate::in_memory::InMemoryWegLi) for tests
+///
+/// Code depending on `&dyn WegLiApi` instead of the concrete client can be tested without HTTP.
+/// See [WegLiApiClient] for the documentation of the individual methods.
+#[async_trait]
+pub trait WegLiApi: Send + Sync {
+    async fn get_notice(&self, notice_token: &str) -> Result<Notice, ApiError>;
+    async fn get_notices(&self) -> Result<Vec<Notice>, ApiError>;
+    async fn get_charge(&self, tbnr: &str) -> Result<Charge, ApiError>;
+    async fn get_charges(&self) -> Result<Vec<Charge>, ApiError>;
+    async fn get_district(&self, zip: &str) -> Result<District, ApiError>;
+    async fn get_districts(&self) -> Result<Vec<District>, ApiError>;
+    async fn get_user_exports(&self) -> Result<Vec<Export>, ApiError>;
+    async fn get_public_exports(&self) -> Result<Vec<Export>, ApiError>;
+    async fn download_export(
+        &self,
+        export: &Export,
+        path: &Path,
+        unzip: bool,
+        download_options: &DownloadOptions,
+    ) -> Result<PathBuf, anyhow::Error>;
+    async fn download_notice_photo(
+        &self,
+        photo: &NoticePhotosJson,
+        path: &Path,
+        download_options: &DownloadOptions,
+    ) -> Result<PathBuf, DownloadError>;
+
+    async fn get_notices_by_token(
+        &self,
+        notice_tokens: Vec<String>,
+        options: &BulkOptions,
+    ) -> Vec<(String, Result<Notice, ApiError>)> {
+        fetch_bulk(notice_tokens, options, |notice_token| async move {
+            let result = self.get_notice(&notice_token).await;
+            (notice_token, result)
+        })
+        .await
+    }
+
+    async fn get_charges_by_tbnr(
+        &self,
+        tbnrs: Vec<String>,
+        options: &BulkOptions,
+    ) -> Vec<(String, Result<Charge, ApiError>)> {
+        fetch_bulk(tbnrs, options, |tbnr| async move {
+            let result = self.get_charge(&tbnr).await;
+            (tbnr, result)
+        })
+        .await
+    }
+
+    async fn get_districts_by_zip(
+        &self,
+        zips: Vec<String>,
+        options: &BulkOptions,
+    ) -> Vec<(String, Result<District, ApiError>)> {
+        fetch_bulk(zips, options, |zip| async move {
+            let result = self.get_district(&zip).await;
+            (zip, result)
+        })
+        .await
+    }
+
+    async fn download_selected_export(
+        &self,
+        selector: &ExportSelector,
+        path: &Path,
+        public: bool,
+        unzip: bool,
+        download_options: &DownloadOptions,
+    ) -> Result<PathBuf, anyhow::Error> {
+        let exports = if public {
+            self.get_public_exports().await
+        } else {
+            self.get_user_exports().await
+        };
+        let export = match exports {
+            Err(error) => return Err(anyhow!(error)),
+            Ok(exports) => match selector.select(&exports) {
+                None => return Err(anyhow!("no export found matching {:?}", selector)),
+                Some(export) => export.clone(),
+            },
+        };
+        self.download_export(&export, path, unzip, download_options)
+            .await
+    }
+
+    async fn download_latest_export(
+        &self,
+        path: &Path,
+        public: bool,
+        unzip: bool,
+    ) -> Result<PathBuf, anyhow::Error> {
+        self.download_latest_export_with_options(path, public, unzip, &DownloadOptions::default())
+            .await
+    }
+
+    async fn download_latest_export_with_options(
+        &self,
+        path: &Path,
+        public: bool,
+        unzip: bool,
+        download_options: &DownloadOptions,
+    ) -> Result<PathBuf, anyhow::Error> {
+        self.download_selected_export(
+            &ExportSelector::Latest,
+            path,
+            public,
+            unzip,
+            download_options,
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl WegLiApi for WegLiApiClient {
+    async fn get_notice(&self, notice_token: &str) -> Result<Notice, ApiError> {
+        WegLiApiClient::get_notice(self, &notice_token.to_string()).await
+    }
+    async fn get_notices(&self) -> Result<Vec<Notice>, ApiError> {
+        WegLiApiClient::get_notices(self).await
+    }
+    async fn get_charge(&self, tbnr: &str) -> Result<Charge, ApiError> {
+        WegLiApiClient::get_charge(self, &tbnr.to_string()).await
+    }
+    async fn get_charges(&self) -> Result<Vec<Charge>, ApiError> {
+        WegLiApiClient::get_charges(self).await
+    }
+    async fn get_district(&self, zip: &str) -> Result<District, ApiError> {
+        WegLiApiClient::get_district(self, &zip.to_string()).await
+    }
+    async fn get_districts(&self) -> Result<Vec<District>, ApiError> {
+        WegLiApiClient::get_districts(self).await
+    }
+    async fn get_user_exports(&self) -> Result<Vec<Export>, ApiError> {
+        WegLiApiClient::get_user_exports(self).await
+    }
+    async fn get_public_exports(&self) -> Result<Vec<Export>, ApiError> {
+        WegLiApiClient::get_public_exports(self).await
+    }
+    async fn download_export(
+        &self,
+        export: &Export,
+        path: &Path,
+        unzip: bool,
+        download_options: &DownloadOptions,
+    ) -> Result<PathBuf, anyhow::Error> {
+        WegLiApiClient::download_export(self, export, path, unzip, download_options).await
+    }
+    async fn download_notice_photo(
+        &self,
+        photo: &NoticePhotosJson,
+        path: &Path,
+        download_options: &DownloadOptions,
+    ) -> Result<PathBuf, DownloadError> {
+        WegLiApiClient::download_notice_photo(self, photo, path, download_options).await
+    }
+    async fn download_selected_export(
+        &self,
+        selector: &ExportSelector,
+        path: &Path,
+        public: bool,
+        unzip: bool,
+        download_options: &DownloadOptions,
+    ) -> Result<PathBuf, anyhow::Error> {
+        WegLiApiClient::download_selected_export(
+            self,
+            selector,
+            path,
+            public,
+            unzip,
+            download_options,
+        )
+        .await
+    }
+}
@@ -0,0 +1,584 @@
+use std::{fs::File, path::Path, sync::Arc};
+
+use anyhow::anyhow;
+use arrow_array::{
+    builder::{ListBuilder, StringBuilder},
+    ArrayRef, BinaryArray, BooleanArray, Float64Array, Int32Array, RecordBatch, StringArray,
+    TimestampMillisecondArray, UInt16Array, UInt32Array, UInt8Array,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, FixedOffset};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::Compression,
+    file::{
+        metadata::KeyValue,
+        properties::{EnabledStatistics, WriterProperties},
+    },
+};
+use serde_json::json;
+
+use crate::types::{charge::Charge, district::District, export::ExportNotice};
+
+/// Name of the GeoParquet geometry column holding WKB encoded points
+pub const GEOMETRY_COLUMN: &str = "geometry";
+
+#[derive(Debug, Clone)]
+pub struct ParquetWriteSettings {
+    /// Maximum number of rows per row group, each row group carries its own column statistics
+    pub max_row_group_size: usize,
+    /// Number of rows converted into one record batch at a time when writing a stream of rows
+    pub batch_size: usize,
+}
+
+pub const DEFAULT_PARQUET_WRITE_SETTINGS: ParquetWriteSettings = ParquetWriteSettings {
+    max_row_group_size: 128 * 1024,
+    batch_size: 8 * 1024,
+};
+
+/// Timestamps are converted to UTC instants, dropping the offset they were recorded with
+///
+/// Where the local time matters, e.g. the hour of day of a notice, the offset is kept in a separate column, see [utc_offset_array].
+fn timestamp_type() -> DataType {
+    DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()))
+}
+
+fn timestamp_array<I>(values: I) -> TimestampMillisecondArray
+where
+    I: IntoIterator<Item = Option<DateTime<FixedOffset>>>,
+{
+    values
+        .into_iter()
+        .map(|val| val.map(|date_time| date_time.timestamp_millis()))
+        .collect::<TimestampMillisecondArray>()
+        .with_timezone("UTC")
+}
+
+/// Offsets of `values` from UTC in seconds, e.g. 3600 for a date recorded in CET, to restore their local time
+fn utc_offset_array<I>(values: I) -> Int32Array
+where
+    I: IntoIterator<Item = DateTime<FixedOffset>>,
+{
+    values
+        .into_iter()
+        .map(|date_time| date_time.offset().local_minus_utc())
+        .collect()
+}
+
+/// Encode a coordinate as well-known binary point, little endian, x being the longitude
+fn wkb_point(latitude: f64, longitude: f64) -> Vec<u8> {
+    let mut wkb = Vec::with_capacity(21);
+    wkb.push(1);
+    wkb.extend_from_slice(&1u32.to_le_bytes());
+    wkb.extend_from_slice(&longitude.to_le_bytes());
+    wkb.extend_from_slice(&latitude.to_le_bytes());
+    wkb
+}
+
+fn geometry_array<I>(coordinates: I) -> BinaryArray
+where
+    I: IntoIterator<Item = Option<(f64, f64)>>,
+{
+    coordinates
+        .into_iter()
+        .map(|val| val.map(|(latitude, longitude)| wkb_point(latitude, longitude)))
+        .collect::<Vec<Option<Vec<u8>>>>()
+        .iter()
+        .map(|val| val.as_deref())
+        .collect()
+}
+
+fn string_list_array<'a, I>(values: I) -> ArrayRef
+where
+    I: IntoIterator<Item = Option<&'a Vec<String>>>,
+{
+    let mut builder = ListBuilder::new(StringBuilder::new());
+    for value in values {
+        match value {
+            None => builder.append_null(),
+            Some(strings) => {
+                for string in strings {
+                    builder.values().append_value(string);
+                }
+                builder.append(true);
+            }
+        }
+    }
+    Arc::new(builder.finish())
+}
+
+fn string_list_type() -> DataType {
+    DataType::List(Arc::new(Field::new("item", DataType::Utf8, true)))
+}
+
+pub fn export_notice_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("start_date", timestamp_type(), false),
+        Field::new("start_date_utc_offset", DataType::Int32, false),
+        Field::new("end_date", timestamp_type(), false),
+        Field::new("end_date_utc_offset", DataType::Int32, false),
+        Field::new("tbnr", DataType::Utf8, false),
+        Field::new("street", DataType::Utf8, false),
+        Field::new("city", DataType::Utf8, false),
+        Field::new("zip", DataType::Utf8, false),
+        Field::new("latitude", DataType::Float64, true),
+        Field::new("longitude", DataType::Float64, true),
+        Field::new(GEOMETRY_COLUMN, DataType::Binary, true),
+    ]))
+}
+
+fn export_notice_coordinates(notice: &ExportNotice) -> Option<(f64, f64)> {
+    match (notice.latitude, notice.longitude) {
+        (Some(latitude), Some(longitude)) => Some((latitude, longitude)),
+        _ => None,
+    }
+}
+
+/// Convert export notices into a record batch of [export_notice_schema]
+///
+/// Timestamps are stored as UTC instants with their offset in seconds in the `*_utc_offset` columns.
+/// The geometry column holds a WKB point if both latitude and longitude are set.
+pub fn export_notices_to_record_batch(
+    notices: &[ExportNotice],
+) -> Result<RecordBatch, anyhow::Error> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(timestamp_array(
+            notices.iter().map(|notice| Some(notice.start_date)),
+        )),
+        Arc::new(utc_offset_array(
+            notices.iter().map(|notice| notice.start_date),
+        )),
+        Arc::new(timestamp_array(
+            notices.iter().map(|notice| Some(notice.end_date)),
+        )),
+        Arc::new(utc_offset_array(
+            notices.iter().map(|notice| notice.end_date),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            notices.iter().map(|notice| &notice.tbnr),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            notices.iter().map(|notice| &notice.street),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            notices.iter().map(|notice| &notice.city),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            notices.iter().map(|notice| &notice.zip),
+        )),
+        Arc::new(Float64Array::from_iter(
+            notices.iter().map(|notice| notice.latitude),
+        )),
+        Arc::new(Float64Array::from_iter(
+            notices.iter().map(|notice| notice.longitude),
+        )),
+        Arc::new(geometry_array(
+            notices.iter().map(export_notice_coordinates),
+        )),
+    ];
+    match RecordBatch::try_new(export_notice_schema(), columns) {
+        Err(error) => Err(anyhow!(error)),
+        Ok(batch) => Ok(batch),
+    }
+}
+
+/// Convert a stream of export notices into record batches of at most `batch_size` rows
+///
+/// Only one batch worth of notices is held in memory at a time, so a whole export can be converted while reading its CSV.
+pub fn export_notice_record_batches<I>(
+    notices: I,
+    batch_size: usize,
+) -> impl Iterator<Item = Result<RecordBatch, anyhow::Error>>
+where
+    I: IntoIterator<Item = ExportNotice>,
+{
+    let mut notices = notices.into_iter();
+    let batch_size = batch_size.max(1);
+    std::iter::from_fn(move || {
+        let chunk: Vec<ExportNotice> = notices.by_ref().take(batch_size).collect();
+        if chunk.is_empty() {
+            return None;
+        }
+        Some(export_notices_to_record_batch(&chunk))
+    })
+}
+
+pub fn charge_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("tbnr", DataType::Utf8, false),
+        Field::new("description", DataType::Utf8, false),
+        Field::new("fine", DataType::Float64, false),
+        Field::new("bkat", DataType::Utf8, false),
+        Field::new("penalty", DataType::Utf8, true),
+        Field::new("fap", DataType::Utf8, true),
+        Field::new("points", DataType::UInt8, true),
+        Field::new("valid_from", timestamp_type(), true),
+        Field::new("valid_to", timestamp_type(), true),
+        Field::new("implementation", DataType::UInt8, true),
+        Field::new("classification", DataType::UInt8, false),
+        Field::new("variant_table_id", DataType::UInt32, true),
+        Field::new("rule_id", DataType::UInt16, false),
+        Field::new("table_id", DataType::UInt32, true),
+        Field::new("required_refinements", DataType::Utf8, false),
+        Field::new("number_required_refinements", DataType::UInt8, false),
+        Field::new("max_fine", DataType::Float64, false),
+        Field::new("created_at", timestamp_type(), false),
+        Field::new("updated_at", timestamp_type(), false),
+    ]))
+}
+
+/// Convert charges into a record batch of [charge_schema]
+pub fn charges_to_record_batch(charges: &[Charge]) -> Result<RecordBatch, anyhow::Error> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            charges.iter().map(|charge| &charge.tbnr),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            charges.iter().map(|charge| &charge.description),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            charges.iter().map(|charge| charge.fine),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            charges.iter().map(|charge| &charge.bkat),
+        )),
+        Arc::new(StringArray::from_iter(
+            charges.iter().map(|charge| charge.penalty.as_deref()),
+        )),
+        Arc::new(StringArray::from_iter(
+            charges.iter().map(|charge| charge.fap.as_deref()),
+        )),
+        Arc::new(UInt8Array::from_iter(
+            charges.iter().map(|charge| charge.points),
+        )),
+        Arc::new(timestamp_array(
+            charges.iter().map(|charge| charge.valid_from),
+        )),
+        Arc::new(timestamp_array(
+            charges.iter().map(|charge| charge.valid_to),
+        )),
+        Arc::new(UInt8Array::from_iter(
+            charges.iter().map(|charge| charge.implementation),
+        )),
+        Arc::new(UInt8Array::from_iter_values(
+            charges.iter().map(|charge| charge.classification),
+        )),
+        Arc::new(UInt32Array::from_iter(
+            charges.iter().map(|charge| charge.variant_table_id),
+        )),
+        Arc::new(UInt16Array::from_iter_values(
+            charges.iter().map(|charge| charge.rule_id),
+        )),
+        Arc::new(UInt32Array::from_iter(
+            charges.iter().map(|charge| charge.table_id),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            charges.iter().map(|charge| &charge.required_refinements),
+        )),
+        Arc::new(UInt8Array::from_iter_values(
+            charges
+                .iter()
+                .map(|charge| charge.number_required_refinements),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            charges.iter().map(|charge| charge.max_fine),
+        )),
+        Arc::new(timestamp_array(
+            charges.iter().map(|charge| Some(charge.created_at)),
+        )),
+        Arc::new(timestamp_array(
+            charges.iter().map(|charge| Some(charge.updated_at)),
+        )),
+    ];
+    match RecordBatch::try_new(charge_schema(), columns) {
+        Err(error) => Err(anyhow!(error)),
+        Ok(batch) => Ok(batch),
+    }
+}
+
+pub fn district_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("name", DataType::Utf8, false),
+        Field::new("zip", DataType::Utf8, false),
+        Field::new("email", DataType::Utf8, false),
+        Field::new("prefixes", string_list_type(), false),
+        Field::new("latitude", DataType::Float64, false),
+        Field::new("longitude", DataType::Float64, false),
+        Field::new("aliases", string_list_type(), true),
+        Field::new("personal_email", DataType::Boolean, false),
+        Field::new("created_at", timestamp_type(), false),
+        Field::new("updated_at", timestamp_type(), false),
+        Field::new(GEOMETRY_COLUMN, DataType::Binary, true),
+    ]))
+}
+
+/// Convert districts into a record batch of [district_schema], including a WKB point geometry column
+pub fn districts_to_record_batch(districts: &[District]) -> Result<RecordBatch, anyhow::Error> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            districts.iter().map(|district| &district.name),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            districts.iter().map(|district| &district.zip),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            districts.iter().map(|district| &district.email),
+        )),
+        string_list_array(districts.iter().map(|district| Some(&district.prefixes))),
+        Arc::new(Float64Array::from_iter_values(
+            districts.iter().map(|district| district.latitude),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            districts.iter().map(|district| district.longitude),
+        )),
+        string_list_array(districts.iter().map(|district| district.aliases.as_ref())),
+        Arc::new(BooleanArray::from_iter(
+            districts
+                .iter()
+                .map(|district| Some(district.personal_email)),
+        )),
+        Arc::new(timestamp_array(
+            districts.iter().map(|district| Some(district.created_at)),
+        )),
+        Arc::new(timestamp_array(
+            districts.iter().map(|district| Some(district.updated_at)),
+        )),
+        Arc::new(geometry_array(
+            districts
+                .iter()
+                .map(|district| Some((district.latitude, district.longitude))),
+        )),
+    ];
+    match RecordBatch::try_new(district_schema(), columns) {
+        Err(error) => Err(anyhow!(error)),
+        Ok(batch) => Ok(batch),
+    }
+}
+
+/// Bounding box of all points written, as `[min_longitude, min_latitude, max_longitude, max_latitude]`
+fn extend_bbox(bbox: &mut Option<[f64; 4]>, batch: &RecordBatch) {
+    let columns = (
+        batch.column_by_name("latitude"),
+        batch.column_by_name("longitude"),
+    );
+    let (latitudes, longitudes) = match columns {
+        (Some(latitudes), Some(longitudes)) => (latitudes, longitudes),
+        _ => return,
+    };
+    let latitudes = latitudes.as_any().downcast_ref::<Float64Array>();
+    let longitudes = longitudes.as_any().downcast_ref::<Float64Array>();
+    let (latitudes, longitudes) = match (latitudes, longitudes) {
+        (Some(latitudes), Some(longitudes)) => (latitudes, longitudes),
+        _ => return,
+    };
+    for (latitude, longitude) in latitudes.iter().zip(longitudes.iter()) {
+        if let (Some(latitude), Some(longitude)) = (latitude, longitude) {
+            *bbox = Some(match bbox {
+                None => [longitude, latitude, longitude, latitude],
+                Some([min_lon, min_lat, max_lon, max_lat]) => [
+                    min_lon.min(longitude),
+                    min_lat.min(latitude),
+                    max_lon.max(longitude),
+                    max_lat.max(latitude),
+                ],
+            });
+        }
+    }
+}
+
+/// Write record batches sharing `schema` to a Parquet file with row group statistics and snappy compression
+///
+/// If the schema contains a [GEOMETRY_COLUMN], GeoParquet metadata describing it as WKB points in longitude/latitude order (OGC:CRS84) is added.
+pub fn write_parquet<I>(
+    path: &Path,
+    schema: SchemaRef,
+    batches: I,
+    settings: &ParquetWriteSettings,
+) -> Result<(), anyhow::Error>
+where
+    I: IntoIterator<Item = Result<RecordBatch, anyhow::Error>>,
+{
+    let file = match File::create(path) {
+        Err(error) => return Err(anyhow!(error)),
+        Ok(val) => val,
+    };
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_statistics_enabled(EnabledStatistics::Page)
+        .set_max_row_group_size(settings.max_row_group_size)
+        .build();
+    let has_geometry = schema.column_with_name(GEOMETRY_COLUMN).is_some();
+    let mut writer = match ArrowWriter::try_new(file, schema, Some(properties)) {
+        Err(error) => return Err(anyhow!(error)),
+        Ok(val) => val,
+    };
+
+    let mut bbox: Option<[f64; 4]> = None;
+    for batch in batches {
+        let batch = batch?;
+        if has_geometry {
+            extend_bbox(&mut bbox, &batch);
+        }
+        if let Err(error) = writer.write(&batch) {
+            return Err(anyhow!(error));
+        }
+    }
+
+    if has_geometry {
+        let mut column = json!({
+            "encoding": "WKB",
+            "geometry_types": ["Point"],
+        });
+        if let Some(bbox) = bbox {
+            column["bbox"] = json!(bbox);
+        }
+        let geo = json!({
+            "version": "1.0.0",
+            "primary_column": GEOMETRY_COLUMN,
+            "columns": { GEOMETRY_COLUMN: column },
+        });
+        writer.append_key_value_metadata(KeyValue::new("geo".to_string(), geo.to_string()));
+    }
+
+    match writer.close() {
+        Err(error) => Err(anyhow!(error)),
+        Ok(_) => Ok(()),
+    }
+}
+
+/// Write a stream of export notices to a GeoParquet file, see [export_notices_to_record_batch] for the columns
+pub fn write_export_notices_parquet<I>(
+    path: &Path,
+    notices: I,
+    settings: &ParquetWriteSettings,
+) -> Result<(), anyhow::Error>
+where
+    I: IntoIterator<Item = ExportNotice>,
+{
+    write_parquet(
+        path,
+        export_notice_schema(),
+        export_notice_record_batches(notices, settings.batch_size),
+        settings,
+    )
+}
+
+pub fn write_charges_parquet(
+    path: &Path,
+    charges: &[Charge],
+    settings: &ParquetWriteSettings,
+) -> Result<(), anyhow::Error> {
+    write_parquet(
+        path,
+        charge_schema(),
+        vec![charges_to_record_batch(charges)],
+        settings,
+    )
+}
+
+pub fn write_districts_parquet(
+    path: &Path,
+    districts: &[District],
+    settings: &ParquetWriteSettings,
+) -> Result<(), anyhow::Error> {
+    write_parquet(
+        path,
+        district_schema(),
+        vec![districts_to_record_batch(districts)],
+        settings,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use arrow_array::{Array, BinaryArray, Int32Array, TimestampMillisecondArray};
+    use parquet::{arrow::arrow_reader::ParquetRecordBatchReaderBuilder, file::reader::FileReader};
+
+    use crate::types::export::ExportNotice;
+
+    use super::{write_export_notices_parquet, ParquetWriteSettings};
+
+    #[test]
+    fn test_write_export_notices_parquet() {
+        let start_date =
+            chrono::DateTime::parse_from_rfc3339("2023-10-25T09:23:00.000+01:00").unwrap();
+        let notices: Vec<ExportNotice> = (0..5)
+            .map(|i| ExportNotice {
+                start_date,
+                end_date: start_date + chrono::Duration::minutes(i),
+                tbnr: "112454".to_string(),
+                street: "Hauptstraße 1".to_string(),
+                city: "Hamburg".to_string(),
+                zip: "20095".to_string(),
+                latitude: if i == 4 { None } else { Some(53.5 + i as f64) },
+                longitude: Some(9.9),
+            })
+            .collect();
+        let dir = std::env::temp_dir().join("weg_li_api_test_write_export_notices_parquet");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notices.parquet");
+
+        write_export_notices_parquet(
+            &path,
+            notices,
+            &ParquetWriteSettings {
+                max_row_group_size: 2,
+                batch_size: 3,
+            },
+        )
+        .unwrap();
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap()).unwrap();
+        let geo = builder
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .unwrap()
+            .iter()
+            .find(|kv| kv.key == "geo")
+            .unwrap()
+            .value
+            .clone()
+            .unwrap();
+        assert!(geo.contains(r#""bbox":[9.9,53.5,9.9,56.5]"#));
+        assert_eq!(builder.metadata().num_row_groups(), 3);
+        let batches: Vec<_> = builder
+            .build()
+            .unwrap()
+            .map(|batch| batch.unwrap())
+            .collect();
+        let start_dates = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<TimestampMillisecondArray>()
+            .unwrap();
+        assert_eq!(start_dates.value(0), start_date.timestamp_millis());
+        let start_date_offsets = batches[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(start_date_offsets.value(0), 3600);
+        let geometries = batches[0]
+            .column(10)
+            .as_any()
+            .downcast_ref::<BinaryArray>()
+            .unwrap();
+        assert!(geometries.is_valid(0));
+        assert!(geometries.is_null(4));
+
+        let row_group =
+            parquet::file::serialized_reader::SerializedFileReader::new(File::open(&path).unwrap())
+                .unwrap();
+        assert!(row_group
+            .metadata()
+            .row_group(0)
+            .column(0)
+            .statistics()
+            .is_some());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
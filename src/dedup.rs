@@ -0,0 +1,449 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, FixedOffset};
+
+use crate::{
+    geo::{haversine_distance, index::NoticeIndex, Located, Positioned},
+    registration::normalize_registration,
+    types::{export::ExportNotice, notice::Notice},
+};
+
+#[derive(Debug, Clone)]
+pub struct NoticeDedupSettings {
+    /// Maximum time between the start dates of duplicates
+    pub time_window: Duration,
+    /// Maximum distance in meters between duplicates
+    pub max_distance: f64,
+}
+
+pub const DEFAULT_NOTICE_DEDUP_SETTINGS: NoticeDedupSettings = NoticeDedupSettings {
+    time_window: Duration::minutes(30),
+    max_distance: 100.0,
+};
+
+#[derive(Debug, Clone)]
+pub struct ExportNoticeDedupSettings {
+    /// Maximum distance in meters between duplicates
+    pub max_distance: f64,
+}
+
+pub const DEFAULT_EXPORT_NOTICE_DEDUP_SETTINGS: ExportNoticeDedupSettings =
+    ExportNoticeDedupSettings { max_distance: 25.0 };
+
+/// Items considered to be the same offense
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup<'a, T> {
+    /// Items in their original order, the first one is kept by [DedupReport::unique]
+    pub items: Vec<&'a T>,
+    /// Mean similarity between 0 and 1 of the pairs of items matched
+    pub score: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DedupReport<'a, T> {
+    /// Groups of duplicates, in the order of their first item
+    pub groups: Vec<DuplicateGroup<'a, T>>,
+    items: &'a [T],
+    duplicate: Vec<bool>,
+}
+
+impl<'a, T> DedupReport<'a, T> {
+    /// Number of items that are duplicates of an earlier item
+    pub fn duplicate_count(&self) -> usize {
+        self.duplicate.iter().filter(|val| **val).count()
+    }
+
+    /// Items without their duplicates, keeping the first item of every group
+    pub fn unique(&self) -> impl Iterator<Item = &'a T> + '_ {
+        self.items
+            .iter()
+            .zip(self.duplicate.iter())
+            .filter(|(_, duplicate)| !**duplicate)
+            .map(|(item, _)| item)
+    }
+}
+
+struct UnionFind {
+    parents: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        UnionFind {
+            parents: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, position: usize) -> usize {
+        let mut root = position;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        let mut position = position;
+        while self.parents[position] != root {
+            let parent = self.parents[position];
+            self.parents[position] = root;
+            position = parent;
+        }
+        root
+    }
+
+    /// Join the sets of `a` and `b`, the smaller position becoming the root
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parents[a.max(b)] = a.min(b);
+        }
+    }
+}
+
+/// Group `items` by the matched pairs of positions with their similarity
+fn report<'a, T>(items: &'a [T], pairs: Vec<(usize, usize, f64)>) -> DedupReport<'a, T> {
+    let mut sets = UnionFind::new(items.len());
+    for (a, b, _) in pairs.iter() {
+        sets.union(*a, *b);
+    }
+    let mut members: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for position in 0..items.len() {
+        members
+            .entry(sets.find(position))
+            .or_default()
+            .push(position);
+    }
+    let mut scores: BTreeMap<usize, (f64, usize)> = BTreeMap::new();
+    for (a, _, score) in pairs.iter() {
+        let entry = scores.entry(sets.find(*a)).or_default();
+        entry.0 += score;
+        entry.1 += 1;
+    }
+
+    let mut duplicate = vec![false; items.len()];
+    let mut groups = vec![];
+    for (root, positions) in members {
+        if positions.len() < 2 {
+            continue;
+        }
+        for position in positions.iter().skip(1) {
+            duplicate[*position] = true;
+        }
+        let (score_sum, pair_count) = scores[&root];
+        groups.push(DuplicateGroup {
+            items: positions.iter().map(|position| &items[*position]).collect(),
+            score: score_sum / pair_count as f64,
+        });
+    }
+    DedupReport {
+        groups,
+        items,
+        duplicate,
+    }
+}
+
+fn proximity(distance: f64, max_distance: f64) -> f64 {
+    if max_distance <= 0.0 {
+        return 1.0;
+    }
+    1.0 - distance / max_distance
+}
+
+/// Find notices of the same registration, spelling variants included, close in time and place
+///
+/// The similarity of a pair is the mean of how close their start dates are relative to the time
+/// window and how close they are relative to the maximum distance.
+/// Notices without a registration are never grouped, as nothing identifies their vehicle.
+pub fn find_duplicate_notices<'a>(
+    notices: &'a [Notice],
+    settings: &NoticeDedupSettings,
+) -> DedupReport<'a, Notice> {
+    let mut registrations: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (position, notice) in notices.iter().enumerate() {
+        let registration = normalize_registration(&notice.registration);
+        if registration.is_empty() {
+            continue;
+        }
+        registrations
+            .entry(registration)
+            .or_default()
+            .push(position);
+    }
+    let window = settings.time_window.num_seconds().max(0) as f64;
+    let mut pairs = vec![];
+    for positions in registrations.values() {
+        for (i, a) in positions.iter().enumerate() {
+            for b in positions.iter().skip(i + 1) {
+                let (notice_a, notice_b) = (&notices[*a], &notices[*b]);
+                let time_delta = (notice_a.start_date - notice_b.start_date)
+                    .num_seconds()
+                    .abs() as f64;
+                let distance = haversine_distance(
+                    notice_a.latitude,
+                    notice_a.longitude,
+                    notice_b.latitude,
+                    notice_b.longitude,
+                );
+                if time_delta > window || distance > settings.max_distance {
+                    continue;
+                }
+                let time_score = if window > 0.0 {
+                    1.0 - time_delta / window
+                } else {
+                    1.0
+                };
+                let score = (time_score + proximity(distance, settings.max_distance)) / 2.0;
+                pairs.push((*a, *b, score));
+            }
+        }
+    }
+    report(notices, pairs)
+}
+
+/// Share of the shorter of two periods covered by both, 1 for two identical instants
+fn overlap(
+    start_a: DateTime<FixedOffset>,
+    end_a: DateTime<FixedOffset>,
+    start_b: DateTime<FixedOffset>,
+    end_b: DateTime<FixedOffset>,
+) -> Option<f64> {
+    let start = start_a.max(start_b);
+    let end = end_a.min(end_b);
+    if start > end {
+        return None;
+    }
+    let shorter = (end_a - start_a).min(end_b - start_b).num_seconds();
+    if shorter <= 0 {
+        return Some(1.0);
+    }
+    Some(((end - start).num_seconds() as f64 / shorter as f64).min(1.0))
+}
+
+/// Find export notices with the same tbnr, close to each other and with overlapping periods
+///
+/// Export notices without coordinates are never duplicates. The similarity of a pair is the mean of
+/// how close they are relative to the maximum distance and how much of the shorter period overlaps.
+pub fn find_duplicate_export_notices<'a>(
+    notices: &'a [ExportNotice],
+    settings: &ExportNoticeDedupSettings,
+) -> DedupReport<'a, ExportNotice> {
+    let index = NoticeIndex::new(
+        notices
+            .iter()
+            .enumerate()
+            .map(|(position, item)| Positioned { position, item }),
+    );
+    let mut pairs = vec![];
+    for (a, notice_a) in notices.iter().enumerate() {
+        let (latitude, longitude) = match notice_a.coordinates() {
+            None => continue,
+            Some(val) => val,
+        };
+        for (candidate, distance) in index.within_radius(latitude, longitude, settings.max_distance)
+        {
+            let (b, notice_b) = (candidate.position, candidate.item);
+            if b <= a || notice_a.tbnr != notice_b.tbnr {
+                continue;
+            }
+            let overlap = match overlap(
+                notice_a.start_date,
+                notice_a.end_date,
+                notice_b.start_date,
+                notice_b.end_date,
+            ) {
+                None => continue,
+                Some(val) => val,
+            };
+            let score = (proximity(distance, settings.max_distance) + overlap) / 2.0;
+            pairs.push((a, b, score));
+        }
+    }
+    report(notices, pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration};
+
+    use crate::types::{
+        export::ExportNotice,
+        notice::{Notice, NoticeJson},
+    };
+
+    use super::{
+        find_duplicate_export_notices, find_duplicate_notices, NoticeDedupSettings,
+        DEFAULT_EXPORT_NOTICE_DEDUP_SETTINGS, DEFAULT_NOTICE_DEDUP_SETTINGS,
+    };
+
+    fn export_notice(
+        tbnr: &str,
+        start_date: &str,
+        end_date: &str,
+        coordinates: Option<(f64, f64)>,
+    ) -> ExportNotice {
+        ExportNotice {
+            start_date: DateTime::parse_from_rfc3339(start_date).unwrap(),
+            end_date: DateTime::parse_from_rfc3339(end_date).unwrap(),
+            tbnr: tbnr.to_string(),
+            street: "Lange Reihe 10".to_string(),
+            city: "Hamburg".to_string(),
+            zip: "20099".to_string(),
+            latitude: coordinates.map(|val| val.0),
+            longitude: coordinates.map(|val| val.1),
+        }
+    }
+
+    fn notice(token: &str, registration: &str, start_date: &str, latitude: f64) -> Notice {
+        let json = format!(
+            r#"{{
+                "token": "{}",
+                "status": "shared",
+                "street": "Lange Reihe 10",
+                "city": "Hamburg",
+                "zip": "20099",
+                "latitude": {},
+                "longitude": 10.012,
+                "registration": "{}",
+                "color": "silver",
+                "brand": "Opel",
+                "charge": {{
+                    "tbnr": "141322",
+                    "description": "Sie parkten auf einem Radweg.",
+                    "fine": "55.0",
+                    "bkat": "§ 2 Abs. 1, § 12 Abs. 4, § 49 StVO; § 24 Abs. 1, 3 Nr. 5 StVG; 52a.1 BKat",
+                    "penalty": null,
+                    "fap": null,
+                    "points": 0,
+                    "valid_from": "2021-11-09T00:00:00.000+01:00",
+                    "valid_to": null,
+                    "implementation": null,
+                    "classification": 5,
+                    "variant_table_id": null,
+                    "rule_id": 272,
+                    "table_id": null,
+                    "required_refinements": "00000000000000000000000000000000",
+                    "number_required_refinements": 0,
+                    "max_fine": "0.0",
+                    "created_at": "2023-09-18T15:30:27.417+02:00",
+                    "updated_at": "2023-09-18T15:30:27.417+02:00"
+                }},
+                "tbnr": "141322",
+                "start_date": "{}",
+                "end_date": "{}",
+                "note": null,
+                "photos": [],
+                "created_at": "{}",
+                "updated_at": "{}",
+                "sent_at": "{}",
+                "vehicle_empty": true,
+                "hazard_lights": false,
+                "expired_tuv": false,
+                "expired_eco": false,
+                "over_2_8_tons": false
+            }}"#,
+            token,
+            latitude,
+            registration,
+            start_date,
+            start_date,
+            start_date,
+            start_date,
+            start_date
+        );
+        Notice::try_from(&serde_json::from_str::<NoticeJson>(&json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let notices = vec![
+            export_notice(
+                "141322",
+                "2024-03-01T08:00:00+01:00",
+                "2024-03-01T08:20:00+01:00",
+                Some((53.5560, 10.0120)),
+            ),
+            // reported again by someone else a few meters away
+            export_notice(
+                "141322",
+                "2024-03-01T08:10:00+01:00",
+                "2024-03-01T08:30:00+01:00",
+                Some((53.5561, 10.0120)),
+            ),
+            // and a third time, chaining into the same group
+            export_notice(
+                "141322",
+                "2024-03-01T08:25:00+01:00",
+                "2024-03-01T08:40:00+01:00",
+                Some((53.5562, 10.0121)),
+            ),
+            // another charge, another day and no coordinates
+            export_notice(
+                "112454",
+                "2024-03-01T08:00:00+01:00",
+                "2024-03-01T08:20:00+01:00",
+                Some((53.5560, 10.0120)),
+            ),
+            export_notice(
+                "141322",
+                "2024-03-02T08:00:00+01:00",
+                "2024-03-02T08:20:00+01:00",
+                Some((53.5560, 10.0120)),
+            ),
+            export_notice(
+                "141322",
+                "2024-03-01T08:00:00+01:00",
+                "2024-03-01T08:20:00+01:00",
+                None,
+            ),
+        ];
+        let report = find_duplicate_export_notices(&notices, &DEFAULT_EXPORT_NOTICE_DEDUP_SETTINGS);
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.groups[0].items.len(), 3);
+        assert!(report.groups[0].score > 0.0 && report.groups[0].score < 1.0);
+        assert_eq!(report.duplicate_count(), 2);
+        let unique: Vec<&ExportNotice> = report.unique().collect();
+        assert_eq!(unique.len(), 4);
+        assert_eq!(unique[0], &notices[0]);
+
+        let notices = vec![
+            notice("a", "HH AB 123", "2024-03-01T08:00:00+01:00", 53.5560),
+            // same vehicle spelled differently, about 55 m away
+            notice("b", "hh-ab123", "2024-03-01T08:15:00+01:00", 53.5565),
+            // same vehicle two hours later
+            notice("c", "HH AB 123", "2024-03-01T10:00:00+01:00", 53.5560),
+            notice("d", "HH AB 124", "2024-03-01T08:00:00+01:00", 53.5560),
+            // notices without registration are not duplicates of each other
+            notice("e", "", "2024-03-01T08:00:00+01:00", 53.5560),
+            notice("f", " ", "2024-03-01T08:00:00+01:00", 53.5560),
+        ];
+        let report = find_duplicate_notices(&notices, &DEFAULT_NOTICE_DEDUP_SETTINGS);
+        assert_eq!(report.groups.len(), 1);
+        let group_tokens: Vec<&str> = report.groups[0]
+            .items
+            .iter()
+            .map(|notice| notice.token.as_str())
+            .collect();
+        assert_eq!(group_tokens, ["a", "b"]);
+        let unique_tokens: Vec<&str> = report
+            .unique()
+            .map(|notice| notice.token.as_str())
+            .collect();
+        assert_eq!(unique_tokens, ["a", "c", "d", "e", "f"]);
+
+        // without a time window only notices starting at the same time are duplicates
+        let settings = NoticeDedupSettings {
+            time_window: Duration::zero(),
+            ..DEFAULT_NOTICE_DEDUP_SETTINGS
+        };
+        let notices = vec![
+            notice("a", "HH AB 123", "2024-03-01T08:00:00+01:00", 53.5560),
+            notice("b", "HH AB 123", "2024-03-01T08:00:00+01:00", 53.5560),
+            notice("c", "HH AB 123", "2024-03-01T08:01:00+01:00", 53.5560),
+        ];
+        let report = find_duplicate_notices(&notices, &settings);
+        assert_eq!(report.groups.len(), 1);
+        assert_eq!(report.groups[0].score, 1.0);
+        let unique_tokens: Vec<&str> = report
+            .unique()
+            .map(|notice| notice.token.as_str())
+            .collect();
+        assert_eq!(unique_tokens, ["a", "c"]);
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+
+use strsim::jaro_winkler;
+
+use crate::{
+    api::{error::ApiError, wegli_api::WegLiApi},
+    types::district::District,
+};
+
+/// Lowest score of a name or alias to be returned by [DistrictDirectory::search]
+pub const SEARCH_MIN_SCORE: f64 = 0.85;
+
+#[derive(Debug, Clone)]
+pub struct DistrictSearchResult<'a> {
+    /// District with the lowest zip of the authority whose name or alias matched
+    pub district: &'a District,
+    /// Name or alias that matched
+    pub matched: &'a str,
+    /// Between 0 and 1, 1 for an exact match ignoring case and umlauts
+    pub score: f64,
+}
+
+/// Lowercase `value`, replace umlauts and ß and collapse everything but letters and digits into single spaces,
+/// e.g. "muenchen" for "München" and "frankfurt am main" for "Frankfurt (Main)"
+pub fn normalize_name(value: &str) -> String {
+    let mut normalized = String::with_capacity(value.len());
+    for c in value.to_lowercase().chars() {
+        match c {
+            'ä' => normalized.push_str("ae"),
+            'ö' => normalized.push_str("oe"),
+            'ü' => normalized.push_str("ue"),
+            'ß' => normalized.push_str("ss"),
+            c if c.is_alphanumeric() => normalized.push(c),
+            _ => normalized.push(' '),
+        }
+    }
+    normalized
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn score(query: &str, candidate: &str) -> f64 {
+    if query == candidate {
+        return 1.0;
+    }
+    let similarity = jaro_winkler(query, candidate);
+    // prefer candidates containing the query as a whole word, e.g. "Frankfurt am Main" for "Frankfurt"
+    if candidate.split(' ').any(|word| word.starts_with(query)) || candidate.starts_with(query) {
+        return similarity.max(0.9 + 0.09 * query.len() as f64 / candidate.len() as f64);
+    }
+    similarity
+}
+
+/// Districts indexed by zip, authority email, registration prefix and name, for lookups without calling the API
+///
+/// The API only looks up single zips and has no search, which gets slow when resolving many notices.
+#[derive(Debug, Clone, Default)]
+pub struct DistrictDirectory {
+    districts: Vec<District>,
+    zips: BTreeMap<String, usize>,
+    emails: BTreeMap<String, Vec<usize>>,
+    prefixes: BTreeMap<String, Vec<usize>>,
+    /// Normalized names and aliases with the position of their district and of the alias
+    names: Vec<(String, usize, Option<usize>)>,
+}
+
+impl DistrictDirectory {
+    pub fn new<I: IntoIterator<Item = District>>(districts: I) -> Self {
+        let mut districts: Vec<District> = districts.into_iter().collect();
+        districts.sort_by(|a, b| a.zip.cmp(&b.zip));
+        let mut directory = DistrictDirectory::default();
+        for (position, district) in districts.iter().enumerate() {
+            directory.zips.insert(district.zip.clone(), position);
+            directory
+                .emails
+                .entry(district.email.to_lowercase())
+                .or_default()
+                .push(position);
+            for prefix in district.prefixes.iter() {
+                directory
+                    .prefixes
+                    .entry(prefix.to_uppercase())
+                    .or_default()
+                    .push(position);
+            }
+            directory
+                .names
+                .push((normalize_name(&district.name), position, None));
+            for (alias_position, alias) in district.aliases.iter().flatten().enumerate() {
+                directory
+                    .names
+                    .push((normalize_name(alias), position, Some(alias_position)));
+            }
+        }
+        directory.districts = districts;
+        directory
+    }
+
+    /// Load all districts from the API
+    pub async fn from_api(client: &dyn WegLiApi) -> Result<Self, ApiError> {
+        Ok(DistrictDirectory::new(client.get_districts().await?))
+    }
+
+    pub fn len(&self) -> usize {
+        self.districts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.districts.is_empty()
+    }
+
+    /// All districts, sorted by zip
+    pub fn districts(&self) -> &[District] {
+        &self.districts
+    }
+
+    pub fn get(&self, zip: &str) -> Option<&District> {
+        self.zips
+            .get(zip)
+            .map(|position| &self.districts[*position])
+    }
+
+    /// Districts of the authority reachable at `email`, ignoring case
+    pub fn districts_by_email(&self, email: &str) -> Vec<&District> {
+        match self.emails.get(&email.to_lowercase()) {
+            None => vec![],
+            Some(positions) => positions
+                .iter()
+                .map(|position| &self.districts[*position])
+                .collect(),
+        }
+    }
+
+    /// Zips served by the authority reachable at `email`, ignoring case
+    pub fn zips_by_email(&self, email: &str) -> Vec<&str> {
+        self.districts_by_email(email)
+            .into_iter()
+            .map(|district| district.zip.as_str())
+            .collect()
+    }
+
+    /// Districts listing the registration prefix (Unterscheidungszeichen) `prefix`, ignoring case
+    pub fn districts_by_prefix(&self, prefix: &str) -> Vec<&District> {
+        match self.prefixes.get(&prefix.to_uppercase()) {
+            None => vec![],
+            Some(positions) => positions
+                .iter()
+                .map(|position| &self.districts[*position])
+                .collect(),
+        }
+    }
+
+    /// Search names and aliases ignoring case and umlauts and tolerating typos, best match first
+    ///
+    /// Returns at most `limit` results with one district per authority email.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<DistrictSearchResult<'_>> {
+        let query = normalize_name(query);
+        if query.is_empty() {
+            return vec![];
+        }
+        let mut best: BTreeMap<String, DistrictSearchResult> = BTreeMap::new();
+        for (name, position, alias_position) in self.names.iter() {
+            let score = score(&query, name);
+            if score < SEARCH_MIN_SCORE {
+                continue;
+            }
+            let district = &self.districts[*position];
+            let matched = match (district.aliases.as_ref(), alias_position) {
+                (Some(aliases), Some(alias_position)) => aliases[*alias_position].as_str(),
+                _ => district.name.as_str(),
+            };
+            let authority = district.email.to_lowercase();
+            // districts are sorted by zip, so the first one kept per authority has the lowest zip
+            let better = match best.get(&authority) {
+                None => true,
+                Some(result) => score > result.score,
+            };
+            if better {
+                best.insert(
+                    authority,
+                    DistrictSearchResult {
+                        district,
+                        matched,
+                        score,
+                    },
+                );
+            }
+        }
+        let mut results: Vec<DistrictSearchResult> = best.into_values().collect();
+        results.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.district.zip.cmp(&b.district.zip))
+        });
+        results.truncate(limit);
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::types::district::District;
+
+    use super::{normalize_name, DistrictDirectory};
+
+    fn district(name: &str, zip: &str, prefixes: &[&str], aliases: Option<&[&str]>) -> District {
+        District {
+            name: name.to_string(),
+            zip: zip.to_string(),
+            email: format!("Ordnungsamt@{}.example.org", normalize_name(name)),
+            prefixes: prefixes.iter().map(|val| val.to_string()).collect(),
+            latitude: 50.0,
+            longitude: 10.0,
+            aliases: aliases.map(|val| val.iter().map(|alias| alias.to_string()).collect()),
+            personal_email: false,
+            created_at: DateTime::parse_from_rfc3339("2023-09-18T15:30:27+02:00").unwrap(),
+            updated_at: DateTime::parse_from_rfc3339("2023-09-18T15:30:27+02:00").unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_district_directory() {
+        let directory = DistrictDirectory::new([
+            district("München", "80469", &["M"], None),
+            district("München", "80331", &["M"], None),
+            district("Frankfurt am Main", "60311", &["F"], Some(&["Mainhattan"])),
+            district("Frankfurt (Oder)", "15230", &["FF"], None),
+            district("Düsseldorf", "40213", &["D"], None),
+        ]);
+        assert_eq!(directory.len(), 5);
+        assert_eq!(directory.get("80469").unwrap().name, "München");
+        assert!(directory.get("99999").is_none());
+        assert_eq!(
+            directory.zips_by_email("ordnungsamt@muenchen.example.org"),
+            ["80331", "80469"]
+        );
+        assert_eq!(directory.districts_by_prefix("ff")[0].zip, "15230");
+
+        let results = directory.search("MUENCHEN", 10);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].district.zip, "80331");
+        assert_eq!(results[0].score, 1.0);
+
+        let results = directory.search("dusseldorf", 10);
+        assert_eq!(results[0].district.name, "Düsseldorf");
+
+        let results = directory.search("frankfurt", 10);
+        assert_eq!(results.len(), 2);
+
+        let results = directory.search("mainhatan", 10);
+        assert_eq!(results[0].matched, "Mainhattan");
+        assert!(directory.search("Hamburg", 10).is_empty());
+    }
+}
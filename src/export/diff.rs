@@ -0,0 +1,296 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::{Hash, Hasher},
+    io,
+};
+
+use anyhow::anyhow;
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+
+use crate::types::export::{ExportNotice, ExportNoticeCsv};
+
+/// Identity of an export notice row across exports: start and end of the observation, the "Tatbestandsnummer" and the coordinates.
+///
+/// Coordinates are compared by their exact bit pattern, as weg.li exports them unchanged from week to week.
+#[derive(Debug, Clone)]
+pub struct ExportNoticeKey {
+    pub start_date: DateTime<FixedOffset>,
+    pub end_date: DateTime<FixedOffset>,
+    pub tbnr: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl From<&ExportNotice> for ExportNoticeKey {
+    fn from(value: &ExportNotice) -> Self {
+        ExportNoticeKey {
+            start_date: value.start_date,
+            end_date: value.end_date,
+            tbnr: value.tbnr.clone(),
+            latitude: value.latitude,
+            longitude: value.longitude,
+        }
+    }
+}
+
+impl PartialEq for ExportNoticeKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.start_date == other.start_date
+            && self.end_date == other.end_date
+            && self.tbnr == other.tbnr
+            && self.latitude.map(f64::to_bits) == other.latitude.map(f64::to_bits)
+            && self.longitude.map(f64::to_bits) == other.longitude.map(f64::to_bits)
+    }
+}
+
+impl Eq for ExportNoticeKey {}
+
+impl Hash for ExportNoticeKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.start_date.hash(state);
+        self.end_date.hash(state);
+        self.tbnr.hash(state);
+        self.latitude.map(f64::to_bits).hash(state);
+        self.longitude.map(f64::to_bits).hash(state);
+    }
+}
+
+/// A row present in both exports under the same key, but with differing street, city or zip
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportNoticeModification {
+    pub previous: ExportNotice,
+    pub current: ExportNotice,
+}
+
+/// Differences between two export datasets, as computed by [diff_export_notices]
+///
+/// Rows are listed in the order they appeared in their respective dataset.
+#[derive(Debug, Clone, Default)]
+pub struct ExportNoticeChangeset {
+    /// Rows only present in the current export
+    pub added: Vec<ExportNotice>,
+    /// Rows only present in the previous export
+    pub removed: Vec<ExportNotice>,
+    /// Rows present in both exports with differing values
+    pub changed: Vec<ExportNoticeModification>,
+    /// Number of rows present in both exports with identical values
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportNoticeChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl std::str::FromStr for ExportNoticeChangeKind {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "added" => Ok(ExportNoticeChangeKind::Added),
+            "removed" => Ok(ExportNoticeChangeKind::Removed),
+            "changed" => Ok(ExportNoticeChangeKind::Changed),
+            _ => Err(anyhow!("'{}' is not a valid ExportNoticeChangeKind", s)),
+        }
+    }
+}
+
+impl fmt::Display for ExportNoticeChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportNoticeChangeKind::Added => write!(f, "added"),
+            ExportNoticeChangeKind::Removed => write!(f, "removed"),
+            ExportNoticeChangeKind::Changed => write!(f, "changed"),
+        }
+    }
+}
+
+/// A changeset line as written by [ExportNoticeChangeset::write_csv]
+///
+/// The notice columns hold the current values for added and changed rows and the previous values for removed rows.
+/// The `previous_*` columns are only set for changed rows.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ExportNoticeChangeCsv {
+    pub change: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub tbnr: String,
+    pub street: String,
+    pub city: String,
+    pub zip: String,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub previous_street: Option<String>,
+    pub previous_city: Option<String>,
+    pub previous_zip: Option<String>,
+}
+
+impl ExportNoticeChangeCsv {
+    fn new(
+        change: ExportNoticeChangeKind,
+        notice: &ExportNotice,
+        previous: Option<&ExportNotice>,
+    ) -> Self {
+        let csv = ExportNoticeCsv::from(notice);
+        ExportNoticeChangeCsv {
+            change: change.to_string(),
+            start_date: csv.start_date,
+            end_date: csv.end_date,
+            tbnr: csv.tbnr,
+            street: csv.street,
+            city: csv.city,
+            zip: csv.zip,
+            latitude: csv.latitude,
+            longitude: csv.longitude,
+            previous_street: previous.map(|val| val.street.clone()),
+            previous_city: previous.map(|val| val.city.clone()),
+            previous_zip: previous.map(|val| val.zip.clone()),
+        }
+    }
+}
+
+impl ExportNoticeChangeset {
+    /// Whether both exports contained the same rows
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Number of added, removed and changed rows
+    pub fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.changed.len()
+    }
+
+    /// Write the changeset as CSV with a header line, one [ExportNoticeChangeCsv] per added, removed or changed row
+    pub fn write_csv<W: io::Write>(&self, writer: W) -> Result<(), anyhow::Error> {
+        let mut csv_writer = csv::Writer::from_writer(writer);
+        let lines = self
+            .added
+            .iter()
+            .map(|notice| ExportNoticeChangeCsv::new(ExportNoticeChangeKind::Added, notice, None))
+            .chain(self.removed.iter().map(|notice| {
+                ExportNoticeChangeCsv::new(ExportNoticeChangeKind::Removed, notice, None)
+            }))
+            .chain(self.changed.iter().map(|modification| {
+                ExportNoticeChangeCsv::new(
+                    ExportNoticeChangeKind::Changed,
+                    &modification.current,
+                    Some(&modification.previous),
+                )
+            }));
+        for line in lines {
+            if let Err(error) = csv_writer.serialize(&line) {
+                return Err(anyhow!(error));
+            }
+        }
+        match csv_writer.flush() {
+            Err(error) => Err(anyhow!(error)),
+            Ok(_) => Ok(()),
+        }
+    }
+}
+
+/// Compute which rows were added, removed or changed between two export datasets
+///
+/// The `previous` rows are collected into a map by key, while the `current` rows are consumed one at a time.
+/// Note that [read_export_notices](super::reader::read_export_notices) reads a whole export into a `Vec`, so both exports are in memory when diffing CSV files.
+/// Rows are matched by their [ExportNoticeKey]. If a key occurs several times, identical rows are matched first.
+pub fn diff_export_notices<P, C>(previous: P, current: C) -> ExportNoticeChangeset
+where
+    P: IntoIterator<Item = ExportNotice>,
+    C: IntoIterator<Item = ExportNotice>,
+{
+    let mut previous_by_key: HashMap<ExportNoticeKey, Vec<(usize, ExportNotice)>> = HashMap::new();
+    for (index, notice) in previous.into_iter().enumerate() {
+        previous_by_key
+            .entry(ExportNoticeKey::from(&notice))
+            .or_default()
+            .push((index, notice));
+    }
+
+    let mut changeset = ExportNoticeChangeset::default();
+    for notice in current {
+        let candidates = match previous_by_key.get_mut(&ExportNoticeKey::from(&notice)) {
+            Some(candidates) if !candidates.is_empty() => candidates,
+            _ => {
+                changeset.added.push(notice);
+                continue;
+            }
+        };
+        match candidates
+            .iter()
+            .position(|(_, candidate)| candidate == &notice)
+        {
+            Some(position) => {
+                candidates.swap_remove(position);
+                changeset.unchanged += 1;
+            }
+            None => {
+                let (_, previous_notice) = candidates.swap_remove(0);
+                changeset.changed.push(ExportNoticeModification {
+                    previous: previous_notice,
+                    current: notice,
+                });
+            }
+        }
+    }
+
+    let mut removed: Vec<(usize, ExportNotice)> = previous_by_key.into_values().flatten().collect();
+    removed.sort_by_key(|(index, _)| *index);
+    changeset.removed = removed.into_iter().map(|(_, notice)| notice).collect();
+
+    changeset
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::{export::ExportNotice, util::export_timestamp_to_date_time};
+
+    use super::diff_export_notices;
+
+    fn export_notice(start_date: &str, tbnr: &str, street: &str) -> ExportNotice {
+        let start_date = export_timestamp_to_date_time(&start_date.to_string()).unwrap();
+        ExportNotice {
+            start_date,
+            end_date: start_date + chrono::Duration::minutes(5),
+            tbnr: tbnr.to_string(),
+            street: street.to_string(),
+            city: "Hamburg".to_string(),
+            zip: "20095".to_string(),
+            latitude: Some(53.5511),
+            longitude: Some(9.9937),
+        }
+    }
+
+    #[test]
+    fn test_diff_export_notices() {
+        let previous = vec![
+            export_notice("2024-03-01 10:00:00 .000+0100", "112454", "Hauptstraße 1"),
+            export_notice("2024-03-02 10:00:00 .000+0100", "112454", "Hauptstraße 2"),
+            export_notice("2024-03-03 10:00:00 .000+0100", "141312", "Hauptstraße 3"),
+        ];
+        let current = vec![
+            export_notice("2024-03-01 10:00:00 .000+0100", "112454", "Hauptstraße 1"),
+            export_notice("2024-03-02 10:00:00 .000+0100", "112454", "Nebenstraße 2"),
+            export_notice("2024-03-04 10:00:00 .000+0100", "141312", "Hauptstraße 4"),
+        ];
+
+        let changeset = diff_export_notices(previous, current);
+        assert_eq!(changeset.unchanged, 1);
+        assert_eq!(changeset.added[0].street, "Hauptstraße 4");
+        assert_eq!(changeset.removed[0].street, "Hauptstraße 3");
+        assert_eq!(changeset.changed[0].previous.street, "Hauptstraße 2");
+        assert_eq!(changeset.changed[0].current.street, "Nebenstraße 2");
+
+        let mut csv = vec![];
+        changeset.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 4);
+        assert!(csv
+            .lines()
+            .any(|line| line.starts_with("changed,")
+                && line.ends_with(",Hauptstraße 2,Hamburg,20095")));
+    }
+}
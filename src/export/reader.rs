@@ -0,0 +1,39 @@
+use std::{fs::File, io, path::Path};
+
+use anyhow::anyhow;
+
+use crate::types::export::{ExportNotice, ExportNoticeCsv};
+
+use super::writer::EXPORT_NOTICE_COLUMNS;
+
+/// Read all rows of a CSV in the format of weg.li's notices export
+///
+/// Fails if the header doesn't match [EXPORT_NOTICE_COLUMNS] or a row can't be parsed.
+pub fn read_export_notices<R: io::Read>(reader: R) -> Result<Vec<ExportNotice>, anyhow::Error> {
+    let mut csv_reader = csv::Reader::from_reader(reader);
+    let headers = match csv_reader.headers() {
+        Err(error) => return Err(anyhow!(error)),
+        Ok(val) => val.clone(),
+    };
+    if !headers.iter().eq(EXPORT_NOTICE_COLUMNS) {
+        return Err(anyhow!("unexpected export columns: {:?}", headers));
+    }
+
+    let mut notices = vec![];
+    for row in csv_reader.deserialize::<ExportNoticeCsv>() {
+        let row = match row {
+            Err(error) => return Err(anyhow!(error)),
+            Ok(val) => val,
+        };
+        notices.push(ExportNotice::try_from(&row)?);
+    }
+    Ok(notices)
+}
+
+/// Read all rows of the CSV file at `path`, see [read_export_notices]
+pub fn read_export_csv(path: &Path) -> Result<Vec<ExportNotice>, anyhow::Error> {
+    match File::open(path) {
+        Err(error) => Err(anyhow!(error)),
+        Ok(file) => read_export_notices(io::BufReader::new(file)),
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    fs::File,
+    io::{self, Seek},
+    path::Path,
+};
+
+use anyhow::anyhow;
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::types::export::{ExportNotice, ExportNoticeCsv};
+
+/// Columns of weg.li's notices export, in order
+pub const EXPORT_NOTICE_COLUMNS: [&str; 8] = [
+    "start_date",
+    "end_date",
+    "tbnr",
+    "street",
+    "city",
+    "zip",
+    "latitude",
+    "longitude",
+];
+
+/// Writes [ExportNotice] rows as CSV in the format of weg.li's notices export
+///
+/// The header line is written on creation, so an empty dataset still results in a valid export.
+pub struct ExportWriter<W: io::Write> {
+    csv_writer: csv::Writer<W>,
+}
+
+impl<W: io::Write> ExportWriter<W> {
+    pub fn new(writer: W) -> Result<Self, anyhow::Error> {
+        let mut csv_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(writer);
+        match csv_writer.write_record(EXPORT_NOTICE_COLUMNS) {
+            Err(error) => Err(anyhow!(error)),
+            Ok(_) => Ok(ExportWriter { csv_writer }),
+        }
+    }
+
+    pub fn write(&mut self, notice: &ExportNotice) -> Result<(), anyhow::Error> {
+        match self.csv_writer.serialize(ExportNoticeCsv::from(notice)) {
+            Err(error) => Err(anyhow!(error)),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    pub fn write_all<'a, I>(&mut self, notices: I) -> Result<(), anyhow::Error>
+    where
+        I: IntoIterator<Item = &'a ExportNotice>,
+    {
+        for notice in notices {
+            self.write(notice)?;
+        }
+        Ok(())
+    }
+
+    /// Flush all rows and return the underlying writer
+    pub fn into_inner(self) -> Result<W, anyhow::Error> {
+        match self.csv_writer.into_inner() {
+            Err(error) => Err(anyhow!(error.into_error())),
+            Ok(writer) => Ok(writer),
+        }
+    }
+}
+
+impl<W: io::Write + Seek> ExportWriter<ZipWriter<W>> {
+    /// Create a writer adding the CSV as `csv_file_name` to a new zip archive, like weg.li distributes its exports
+    ///
+    /// Call [finish_zip](ExportWriter::finish_zip) once all rows are written.
+    pub fn new_zipped(writer: W, csv_file_name: &str) -> Result<Self, anyhow::Error> {
+        let mut zip_writer = ZipWriter::new(writer);
+        if let Err(error) = zip_writer.start_file(csv_file_name, SimpleFileOptions::default()) {
+            return Err(anyhow!(error));
+        }
+        ExportWriter::new(zip_writer)
+    }
+
+    /// Flush all rows, finish the zip archive and return the underlying writer
+    pub fn finish_zip(self) -> Result<W, anyhow::Error> {
+        match self.into_inner() {
+            Err(error) => Err(error),
+            Ok(zip_writer) => match zip_writer.finish() {
+                Err(error) => Err(anyhow!(error)),
+                Ok(writer) => Ok(writer),
+            },
+        }
+    }
+}
+
+/// Write `notices` to a new CSV file at `path` in the format of weg.li's notices export
+pub fn write_export_csv<'a, I>(path: &Path, notices: I) -> Result<(), anyhow::Error>
+where
+    I: IntoIterator<Item = &'a ExportNotice>,
+{
+    let file = match File::create(path) {
+        Err(error) => return Err(anyhow!(error)),
+        Ok(val) => val,
+    };
+    let mut writer = ExportWriter::new(io::BufWriter::new(file))?;
+    writer.write_all(notices)?;
+    writer.into_inner().map(|_| ())
+}
+
+/// Write `notices` to a new zip archive at `path`, containing a single CSV named `csv_file_name` in the format of weg.li's notices export
+pub fn write_export_zip<'a, I>(
+    path: &Path,
+    csv_file_name: &str,
+    notices: I,
+) -> Result<(), anyhow::Error>
+where
+    I: IntoIterator<Item = &'a ExportNotice>,
+{
+    let file = match File::create(path) {
+        Err(error) => return Err(anyhow!(error)),
+        Ok(val) => val,
+    };
+    let mut writer = ExportWriter::new_zipped(file, csv_file_name)?;
+    writer.write_all(notices)?;
+    writer.finish_zip().map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::types::export::{ExportNotice, ExportNoticeCsv};
+
+    use super::ExportWriter;
+
+    #[test]
+    fn test_export_writer() {
+        let start_date =
+            chrono::DateTime::parse_from_rfc3339("2023-10-25T09:23:00.000+01:00").unwrap();
+        let notices = vec![ExportNotice {
+            start_date,
+            end_date: start_date + chrono::Duration::minutes(18),
+            tbnr: "112454".to_string(),
+            street: "Hauptstraße 1".to_string(),
+            city: "Metropolis".to_string(),
+            zip: "12345".to_string(),
+            latitude: Some(53.5511),
+            longitude: None,
+        }];
+
+        let mut writer = ExportWriter::new(vec![]).unwrap();
+        writer.write_all(&notices).unwrap();
+        let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            csv,
+            "start_date,end_date,tbnr,street,city,zip,latitude,longitude\n\
+            2023-10-25 09:23:00 .000+0100,2023-10-25 09:41:00 .000+0100,112454,Hauptstraße 1,Metropolis,12345,53.5511,\n"
+        );
+
+        let mut writer = ExportWriter::new_zipped(Cursor::new(vec![]), "notices.csv").unwrap();
+        writer.write_all(&notices).unwrap();
+        let zip = writer.finish_zip().unwrap();
+        let mut archive = zip::ZipArchive::new(zip).unwrap();
+        let mut reader = csv::Reader::from_reader(archive.by_name("notices.csv").unwrap());
+        let rows: Vec<ExportNoticeCsv> = reader.deserialize().map(|row| row.unwrap()).collect();
+        assert_eq!(ExportNotice::try_from(&rows[0]).unwrap(), notices[0]);
+    }
+}
@@ -0,0 +1,509 @@
+use std::{fs::File, io, path::Path};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, TimeZone};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+
+use crate::types::{
+    charge::{Charge, ChargeJson},
+    district::{District, DistrictJson},
+    export::ExportNotice,
+    notice::{Notice, NoticeJson, NoticePhotosJson, NoticeStatus},
+};
+
+struct CityFixture {
+    name: &'static str,
+    prefix: &'static str,
+    zips: &'static [&'static str],
+    latitude: f64,
+    longitude: f64,
+}
+
+const CITIES: [CityFixture; 12] = [
+    CityFixture {
+        name: "Hamburg",
+        prefix: "HH",
+        zips: &["20095", "20099", "20354", "22765"],
+        latitude: 53.5503,
+        longitude: 9.9930,
+    },
+    CityFixture {
+        name: "Berlin",
+        prefix: "B",
+        zips: &["10115", "10178", "10997", "12043"],
+        latitude: 52.5200,
+        longitude: 13.4050,
+    },
+    CityFixture {
+        name: "München",
+        prefix: "M",
+        zips: &["80331", "80469", "80802", "81667"],
+        latitude: 48.1372,
+        longitude: 11.5755,
+    },
+    CityFixture {
+        name: "Köln",
+        prefix: "K",
+        zips: &["50667", "50672", "50823"],
+        latitude: 50.9384,
+        longitude: 6.9599,
+    },
+    CityFixture {
+        name: "Frankfurt am Main",
+        prefix: "F",
+        zips: &["60311", "60313", "60486"],
+        latitude: 50.1109,
+        longitude: 8.6821,
+    },
+    CityFixture {
+        name: "Stuttgart",
+        prefix: "S",
+        zips: &["70173", "70176", "70190"],
+        latitude: 48.7784,
+        longitude: 9.1800,
+    },
+    CityFixture {
+        name: "Düsseldorf",
+        prefix: "D",
+        zips: &["40210", "40213", "40477"],
+        latitude: 51.2254,
+        longitude: 6.7763,
+    },
+    CityFixture {
+        name: "Leipzig",
+        prefix: "L",
+        zips: &["04103", "04109", "04277"],
+        latitude: 51.3397,
+        longitude: 12.3731,
+    },
+    CityFixture {
+        name: "Dresden",
+        prefix: "DD",
+        zips: &["01067", "01069", "01099"],
+        latitude: 51.0504,
+        longitude: 13.7373,
+    },
+    CityFixture {
+        name: "Hannover",
+        prefix: "H",
+        zips: &["30159", "30161", "30169"],
+        latitude: 52.3744,
+        longitude: 9.7386,
+    },
+    CityFixture {
+        name: "Bremen",
+        prefix: "HB",
+        zips: &["28195", "28203", "28209"],
+        latitude: 53.0758,
+        longitude: 8.8072,
+    },
+    CityFixture {
+        name: "Nürnberg",
+        prefix: "N",
+        zips: &["90402", "90403", "90429"],
+        latitude: 49.4521,
+        longitude: 11.0767,
+    },
+];
+
+/// Registration prefixes of surrounding districts, so that not every vehicle is local
+const OTHER_PREFIXES: [&str; 10] = ["PI", "OD", "RZ", "TÜ", "ES", "LB", "BN", "SU", "EBE", "FFB"];
+
+/// Common charges of weg.li notices: tbnr, description, fine in Euros, points
+const CHARGES: [(&str, &str, f64, u8); 9] = [
+    (
+        "112402",
+        "Sie hielten im absoluten Haltverbot (Zeichen 283).",
+        20.0,
+        0,
+    ),
+    (
+        "112454",
+        "Sie parkten verbotswidrig auf dem Gehweg.",
+        55.0,
+        0,
+    ),
+    ("112262", "Sie parkten in zweiter Reihe.", 55.0, 0),
+    (
+        "141312",
+        "Sie parkten auf einem Schutzstreifen für den Radverkehr.",
+        55.0,
+        0,
+    ),
+    ("141322", "Sie parkten auf einem Radweg.", 55.0, 0),
+    (
+        "112052",
+        "Sie parkten im Bereich einer Grundstücksein- oder -ausfahrt.",
+        10.0,
+        0,
+    ),
+    (
+        "112102",
+        "Sie parkten vor einer Bordsteinabsenkung.",
+        10.0,
+        0,
+    ),
+    (
+        "112152",
+        "Sie parkten weniger als 5 Meter vor einer Kreuzung oder Einmündung.",
+        35.0,
+        0,
+    ),
+    (
+        "142678",
+        "Sie parkten auf einem Fußgängerüberweg und behinderten dadurch andere.",
+        100.0,
+        1,
+    ),
+];
+
+const STREETS: [&str; 12] = [
+    "Hauptstraße",
+    "Bahnhofstraße",
+    "Schulstraße",
+    "Gartenstraße",
+    "Dorfstraße",
+    "Bergstraße",
+    "Lindenstraße",
+    "Kirchstraße",
+    "Goethestraße",
+    "Schillerstraße",
+    "Marktplatz",
+    "Am Stadtpark",
+];
+
+const COLORS: [&str; 8] = [
+    "black", "white", "silver", "gray", "blue", "red", "green", "brown",
+];
+
+const BRANDS: [&str; 10] = [
+    "Volkswagen",
+    "Mercedes-Benz",
+    "BMW",
+    "Audi",
+    "Opel",
+    "Ford",
+    "Skoda",
+    "Renault",
+    "Toyota",
+    "Hyundai",
+];
+
+/// Letters allowed in the identification part of German registrations
+const REGISTRATION_LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPRSTUVWXYZ";
+
+#[derive(Debug, Clone)]
+pub struct GeneratorSettings {
+    /// Seed of the random number generator, the same seed always produces the same data
+    pub seed: u64,
+    /// Number of notices to generate
+    pub notice_count: usize,
+    /// Start of the time range offenses are spread over
+    pub from: DateTime<FixedOffset>,
+    /// End of the time range offenses are spread over
+    pub to: DateTime<FixedOffset>,
+}
+
+/// Generates realistic looking weg.li data, reproducible for a given seed
+///
+/// Notices are located in a fixed set of large German cities, with zip codes, coordinates,
+/// registration prefixes and district emails matching the city.
+pub struct Generator {
+    settings: GeneratorSettings,
+    rng: ChaCha8Rng,
+}
+
+/// Offset of German local time at `date_time`, approximating daylight saving time by month
+fn german_offset(date_time: &DateTime<FixedOffset>) -> FixedOffset {
+    if (4..=10).contains(&date_time.month()) {
+        FixedOffset::east_opt(2 * 3600).unwrap()
+    } else {
+        FixedOffset::east_opt(3600).unwrap()
+    }
+}
+
+fn to_local(date_time: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+    date_time.with_timezone(&german_offset(&date_time))
+}
+
+fn fixed_timestamp() -> DateTime<FixedOffset> {
+    FixedOffset::east_opt(3600)
+        .unwrap()
+        .with_ymd_and_hms(2023, 9, 18, 15, 30, 27)
+        .unwrap()
+}
+
+impl Generator {
+    pub fn new(settings: GeneratorSettings) -> Self {
+        Generator {
+            rng: ChaCha8Rng::seed_from_u64(settings.seed),
+            settings,
+        }
+    }
+
+    /// All charges notices are generated with
+    pub fn charges(&self) -> Vec<Charge> {
+        CHARGES
+            .iter()
+            .map(|(tbnr, description, fine, points)| Charge {
+                tbnr: tbnr.to_string(),
+                description: description.to_string(),
+                fine: *fine,
+                bkat: "§ 12, § 49 StVO; § 24 Abs. 1, 3 Nr. 5 StVG".to_string(),
+                penalty: None,
+                fap: None,
+                points: Some(*points),
+                valid_from: Some(fixed_timestamp()),
+                valid_to: None,
+                implementation: None,
+                classification: 5,
+                variant_table_id: None,
+                rule_id: 272,
+                table_id: None,
+                required_refinements: "00000000000000000000000000000000".to_string(),
+                number_required_refinements: 0,
+                max_fine: 0.0,
+                created_at: fixed_timestamp(),
+                updated_at: fixed_timestamp(),
+            })
+            .collect()
+    }
+
+    /// One district per zip code notices are generated in
+    pub fn districts(&self) -> Vec<District> {
+        CITIES
+            .iter()
+            .flat_map(|city| {
+                city.zips.iter().map(move |zip| District {
+                    name: city.name.to_string(),
+                    zip: zip.to_string(),
+                    email: format!(
+                        "ordnungsamt@{}.example.org",
+                        city.name.to_lowercase().replace(' ', "-")
+                    ),
+                    prefixes: vec![city.prefix.to_string()],
+                    latitude: city.latitude,
+                    longitude: city.longitude,
+                    aliases: None,
+                    personal_email: false,
+                    created_at: fixed_timestamp(),
+                    updated_at: fixed_timestamp(),
+                })
+            })
+            .collect()
+    }
+
+    fn registration(&mut self, local_prefix: &str) -> String {
+        let prefix = if self.rng.gen_bool(0.7) {
+            local_prefix
+        } else {
+            OTHER_PREFIXES.choose(&mut self.rng).unwrap()
+        };
+        // at most 8 characters besides the separators
+        let max_letters = (6 - prefix.chars().count()).clamp(1, 2);
+        let letter_count = self.rng.gen_range(1..=max_letters);
+        let letters: String = (0..letter_count)
+            .map(|_| *REGISTRATION_LETTERS.choose(&mut self.rng).unwrap() as char)
+            .collect();
+        let max_digits = (7 - prefix.chars().count() - letter_count).min(4);
+        let digit_count = self.rng.gen_range(1..=max_digits);
+        let number: u32 = self
+            .rng
+            .gen_range(10u32.pow(digit_count as u32 - 1)..10u32.pow(digit_count as u32));
+        let suffix = match self.rng.gen_range(0..100) {
+            0..=2 => "E",
+            3 => "H",
+            _ => "",
+        };
+        format!("{} {} {}{}", prefix, letters, number, suffix)
+    }
+
+    /// Generate a single notice
+    pub fn notice(&mut self) -> Notice {
+        let city = &CITIES[self.rng.gen_range(0..CITIES.len())];
+        let zip = city.zips.choose(&mut self.rng).unwrap().to_string();
+        let (tbnr, ..) = CHARGES[self.rng.gen_range(0..CHARGES.len())];
+        let charge = self
+            .charges()
+            .into_iter()
+            .find(|charge| charge.tbnr == tbnr)
+            .unwrap();
+
+        let range_seconds = (self.settings.to - self.settings.from).num_seconds().max(1);
+        let start_date =
+            to_local(self.settings.from + Duration::seconds(self.rng.gen_range(0..range_seconds)));
+        let end_date = start_date + Duration::minutes(self.rng.gen_range(3..=30));
+        let created_at = end_date + Duration::seconds(self.rng.gen_range(30..=600));
+        let sent_at = created_at + Duration::minutes(self.rng.gen_range(5..=2 * 24 * 60));
+        let status = match self.rng.gen_range(0..100) {
+            0..=59 => NoticeStatus::SHARED,
+            60..=84 => NoticeStatus::OPEN,
+            85..=94 => NoticeStatus::ANALYZING,
+            _ => NoticeStatus::DISABLED,
+        };
+        let token: String = (0..12)
+            .map(|_| format!("{:x}", self.rng.gen_range(0..16)))
+            .collect();
+
+        Notice {
+            photos: vec![NoticePhotosJson {
+                filename: format!("{}.jpg", token),
+                url: format!("https://www.weg.li/photos/{}.jpg", token),
+            }],
+            token,
+            status,
+            street: format!(
+                "{} {}",
+                STREETS.choose(&mut self.rng).unwrap(),
+                self.rng.gen_range(1..=120)
+            ),
+            city: city.name.to_string(),
+            zip,
+            latitude: city.latitude + self.rng.gen_range(-0.03..0.03),
+            longitude: city.longitude + self.rng.gen_range(-0.05..0.05),
+            registration: self.registration(city.prefix),
+            color: COLORS.choose(&mut self.rng).unwrap().to_string(),
+            brand: BRANDS.choose(&mut self.rng).unwrap().to_string(),
+            tbnr: tbnr.to_string(),
+            charge,
+            start_date,
+            end_date,
+            note: None,
+            created_at,
+            updated_at: if status == NoticeStatus::SHARED {
+                sent_at
+            } else {
+                created_at
+            },
+            sent_at,
+            vehicle_empty: self.rng.gen_bool(0.8),
+            hazard_lights: self.rng.gen_bool(0.1),
+            expired_tuv: self.rng.gen_bool(0.02),
+            expired_eco: self.rng.gen_bool(0.01),
+            over_2_8_tons: self.rng.gen_bool(0.05),
+        }
+    }
+
+    /// Generate `notice_count` notices, sorted by start date
+    pub fn notices(&mut self) -> Vec<Notice> {
+        let mut notices: Vec<Notice> = (0..self.settings.notice_count)
+            .map(|_| self.notice())
+            .collect();
+        notices.sort_by_key(|notice| notice.start_date);
+        notices
+    }
+
+    /// Generate `notice_count` notices as rows of a weg.li export
+    pub fn export_notices(&mut self) -> Vec<ExportNotice> {
+        self.notices().iter().map(ExportNotice::from).collect()
+    }
+}
+
+fn write_json<T: Serialize>(path: &Path, items: &[T]) -> Result<(), anyhow::Error> {
+    let file = match File::create(path) {
+        Err(error) => return Err(anyhow!(error)),
+        Ok(val) => val,
+    };
+    match serde_json::to_writer(io::BufWriter::new(file), items) {
+        Err(error) => Err(anyhow!(error)),
+        Ok(_) => Ok(()),
+    }
+}
+
+/// Write `notices` to `path` as returned by the API's notices endpoint
+pub fn write_notices_json(path: &Path, notices: &[Notice]) -> Result<(), anyhow::Error> {
+    let notices: Vec<NoticeJson> = notices.iter().map(NoticeJson::from).collect();
+    write_json(path, &notices)
+}
+
+/// Write `charges` to `path` as returned by the API's charges endpoint
+pub fn write_charges_json(path: &Path, charges: &[Charge]) -> Result<(), anyhow::Error> {
+    let charges: Vec<ChargeJson> = charges.iter().map(ChargeJson::from).collect();
+    write_json(path, &charges)
+}
+
+/// Write `districts` to `path` as returned by the API's districts endpoint
+pub fn write_districts_json(path: &Path, districts: &[District]) -> Result<(), anyhow::Error> {
+    let districts: Vec<DistrictJson> = districts.iter().map(DistrictJson::from).collect();
+    write_json(path, &districts)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::DateTime;
+
+    use crate::{
+        export::{reader::read_export_csv, writer::write_export_csv},
+        types::notice::{Notice, NoticeJson},
+    };
+
+    use super::{write_notices_json, Generator, GeneratorSettings};
+
+    fn settings(seed: u64) -> GeneratorSettings {
+        GeneratorSettings {
+            seed,
+            notice_count: 200,
+            from: DateTime::parse_from_rfc3339("2024-01-01T00:00:00+01:00").unwrap(),
+            to: DateTime::parse_from_rfc3339("2024-07-01T00:00:00+02:00").unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_generator() {
+        let notices = Generator::new(settings(7)).notices();
+        let same_seed = Generator::new(settings(7)).notices();
+        let other_seed = Generator::new(settings(8)).notices();
+        assert_eq!(notices.len(), 200);
+        assert!(notices
+            .iter()
+            .zip(same_seed.iter())
+            .all(|(a, b)| a.token == b.token && a.registration == b.registration));
+        assert_ne!(notices[0].token, other_seed[0].token);
+
+        let generator = Generator::new(settings(7));
+        let zips: Vec<String> = generator
+            .districts()
+            .into_iter()
+            .map(|district| district.zip)
+            .collect();
+        let tbnrs: Vec<String> = generator
+            .charges()
+            .into_iter()
+            .map(|charge| charge.tbnr)
+            .collect();
+        for notice in &notices {
+            assert!(zips.contains(&notice.zip));
+            assert!(tbnrs.contains(&notice.tbnr));
+            assert_eq!(notice.charge.tbnr, notice.tbnr);
+            assert!((47.2..55.1).contains(&notice.latitude));
+            assert!((5.8..15.1).contains(&notice.longitude));
+            assert!(notice.start_date >= settings(7).from && notice.start_date < settings(7).to);
+            assert!(notice.end_date > notice.start_date);
+            let parts: Vec<&str> = notice.registration.split(' ').collect();
+            assert_eq!(parts.len(), 3);
+            assert!(parts.concat().trim_end_matches(['E', 'H']).len() <= 8);
+        }
+
+        let dir = std::env::temp_dir().join("weg_li_api_test_generator");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        write_notices_json(&dir.join("notices.json"), &notices).unwrap();
+        let json: Vec<NoticeJson> =
+            serde_json::from_str(&fs::read_to_string(dir.join("notices.json")).unwrap()).unwrap();
+        assert_eq!(
+            Notice::try_from(&json[0]).unwrap().start_date,
+            notices[0].start_date
+        );
+        let export_notices = Generator::new(settings(7)).export_notices();
+        write_export_csv(&dir.join("notices.csv"), &export_notices).unwrap();
+        assert_eq!(
+            read_export_csv(&dir.join("notices.csv")).unwrap(),
+            export_notices
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, VecDeque},
+};
+
+use chrono::Timelike;
+
+use crate::types::{export::ExportNotice, util::street_without_house_number};
+
+use super::{index::NoticeIndex, Located, Positioned};
+
+#[derive(Debug, Clone)]
+pub struct HotspotSettings {
+    /// Distance in meters within which notices count as neighbors
+    pub radius: f64,
+    /// Minimum number of notices within `radius` of a notice, itself included, to start or extend a hotspot
+    pub min_notices: usize,
+    /// Number of most frequent tbnrs and streets reported per hotspot
+    pub top: usize,
+}
+
+pub const DEFAULT_HOTSPOT_SETTINGS: HotspotSettings = HotspotSettings {
+    radius: 50.0,
+    min_notices: 5,
+    top: 3,
+};
+
+/// Group of notices close to each other
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hotspot {
+    /// Mean latitude of the notices
+    pub latitude: f64,
+    /// Mean longitude of the notices
+    pub longitude: f64,
+    pub count: usize,
+    /// Most frequent tbnrs with their number of notices, most frequent first
+    pub tbnrs: Vec<(String, usize)>,
+    /// Most frequent street names, without house numbers, with their number of notices
+    pub streets: Vec<(String, usize)>,
+    /// Number of notices by hour of the day of their start date, in the offset the date was recorded with
+    pub hours: [usize; 24],
+}
+
+fn most_frequent<I: IntoIterator<Item = String>>(values: I, top: usize) -> Vec<(String, usize)> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for value in values {
+        *counts.entry(value).or_default() += 1;
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    // stable sort keeps equally frequent values in alphabetical order
+    counts.sort_by_key(|(_, count)| Reverse(*count));
+    counts.truncate(top);
+    counts
+}
+
+fn hotspot(notices: &[&ExportNotice], top: usize) -> Hotspot {
+    let mut latitude = 0.0;
+    let mut longitude = 0.0;
+    let mut hours = [0; 24];
+    for notice in notices {
+        if let Some((notice_latitude, notice_longitude)) = notice.coordinates() {
+            latitude += notice_latitude;
+            longitude += notice_longitude;
+        }
+        hours[notice.start_date.hour() as usize] += 1;
+    }
+    Hotspot {
+        latitude: latitude / notices.len() as f64,
+        longitude: longitude / notices.len() as f64,
+        count: notices.len(),
+        tbnrs: most_frequent(notices.iter().map(|notice| notice.tbnr.clone()), top),
+        streets: most_frequent(
+            notices
+                .iter()
+                .map(|notice| street_without_house_number(&notice.street)),
+            top,
+        ),
+        hours,
+    }
+}
+
+/// Cluster `notices` into hotspots with DBSCAN, measuring great-circle distances in meters
+///
+/// Notices without coordinates and notices not close enough to a hotspot are ignored.
+/// Hotspots are sorted by their number of notices, largest first.
+pub fn find_hotspots(notices: &[ExportNotice], settings: &HotspotSettings) -> Vec<Hotspot> {
+    let index = NoticeIndex::new(
+        notices
+            .iter()
+            .enumerate()
+            .map(|(position, item)| Positioned { position, item }),
+    );
+    let neighbors = |position: usize| -> Vec<usize> {
+        match notices[position].coordinates() {
+            None => vec![],
+            Some((latitude, longitude)) => index
+                .within_radius(latitude, longitude, settings.radius)
+                .into_iter()
+                .map(|(item, _)| item.position)
+                .collect(),
+        }
+    };
+
+    let mut visited = vec![false; notices.len()];
+    let mut clusters: Vec<Option<usize>> = vec![None; notices.len()];
+    let mut cluster_count = 0;
+    for position in 0..notices.len() {
+        if visited[position] {
+            continue;
+        }
+        visited[position] = true;
+        let seeds = neighbors(position);
+        if seeds.len() < settings.min_notices {
+            continue;
+        }
+        let cluster = cluster_count;
+        cluster_count += 1;
+        clusters[position] = Some(cluster);
+        let mut queue = VecDeque::from(seeds);
+        while let Some(neighbor) = queue.pop_front() {
+            if clusters[neighbor].is_none() {
+                clusters[neighbor] = Some(cluster);
+            }
+            if visited[neighbor] {
+                continue;
+            }
+            visited[neighbor] = true;
+            let next = neighbors(neighbor);
+            if next.len() >= settings.min_notices {
+                queue.extend(next);
+            }
+        }
+    }
+
+    let mut members: Vec<Vec<&ExportNotice>> = vec![vec![]; cluster_count];
+    for (position, cluster) in clusters.iter().enumerate() {
+        if let Some(cluster) = cluster {
+            members[*cluster].push(&notices[position]);
+        }
+    }
+    let mut hotspots: Vec<Hotspot> = members
+        .iter()
+        .map(|notices| hotspot(notices, settings.top))
+        .collect();
+    hotspots.sort_by_key(|hotspot| Reverse(hotspot.count));
+    hotspots
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::types::export::ExportNotice;
+
+    use super::{find_hotspots, DEFAULT_HOTSPOT_SETTINGS};
+
+    fn export_notice(
+        tbnr: &str,
+        street: &str,
+        start_date: &str,
+        coordinates: Option<(f64, f64)>,
+    ) -> ExportNotice {
+        ExportNotice {
+            start_date: DateTime::parse_from_rfc3339(start_date).unwrap(),
+            end_date: DateTime::parse_from_rfc3339(start_date).unwrap(),
+            tbnr: tbnr.to_string(),
+            street: street.to_string(),
+            city: "Hamburg".to_string(),
+            zip: "20095".to_string(),
+            latitude: coordinates.map(|val| val.0),
+            longitude: coordinates.map(|val| val.1),
+        }
+    }
+
+    #[test]
+    fn test_find_hotspots() {
+        let mut notices = vec![];
+        // six notices within about 30 m on a bike lane in the morning
+        for i in 0..6 {
+            notices.push(export_notice(
+                if i < 4 { "141322" } else { "112454" },
+                &format!("Lange Reihe {}", 10 + i),
+                &format!("2024-03-0{}T08:15:00+01:00", i + 1),
+                Some((53.5560 + i as f64 * 0.00005, 10.0120)),
+            ));
+        }
+        // five notices on a sidewalk about 2 km away in the evening
+        for i in 0..5 {
+            notices.push(export_notice(
+                "112454",
+                "Schanzenstraße 3",
+                "2024-03-01T19:40:00+01:00",
+                Some((53.5630, 9.9630 + i as f64 * 0.00005)),
+            ));
+        }
+        // an isolated notice and one without coordinates
+        notices.push(export_notice(
+            "112454",
+            "Elbchaussee 1",
+            "2024-03-01T12:00:00+01:00",
+            Some((53.5450, 9.9300)),
+        ));
+        notices.push(export_notice(
+            "112454",
+            "Unbekannt",
+            "2024-03-01T12:00:00+01:00",
+            None,
+        ));
+
+        let hotspots = find_hotspots(&notices, &DEFAULT_HOTSPOT_SETTINGS);
+        assert_eq!(hotspots.len(), 2);
+        assert_eq!(hotspots[0].count, 6);
+        assert!((hotspots[0].latitude - 53.556125).abs() < 1e-9);
+        assert_eq!(
+            hotspots[0].tbnrs,
+            [("141322".to_string(), 4), ("112454".to_string(), 2)]
+        );
+        assert_eq!(hotspots[0].streets, [("Lange Reihe".to_string(), 6)]);
+        assert_eq!(hotspots[0].hours[8], 6);
+        assert_eq!(hotspots[1].count, 5);
+        assert_eq!(hotspots[1].hours[19], 5);
+    }
+}
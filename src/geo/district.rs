@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+
+use crate::types::{district::District, notice::Notice};
+
+use super::{index::NoticeIndex, Located};
+
+/// Distance in meters from the nearest district at which the confidence of a match is halved
+pub const DISTRICT_CONFIDENCE_DISTANCE: f64 = 5000.0;
+
+/// Number of nearest districts searched for a competing authority
+///
+/// If all of them belong to the same authority, the match is treated as uncontested.
+const COMPETING_DISTRICTS: usize = 16;
+
+impl Located for District {
+    fn coordinates(&self) -> Option<(f64, f64)> {
+        Some((self.latitude, self.longitude))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DistrictMatch<'a> {
+    pub district: &'a District,
+    /// Distance in meters between the coordinate and the district's coordinate
+    pub distance: f64,
+    /// Between 0 and 1, lower if the coordinate is far from the district or about as close to another authority
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZipCheck {
+    /// The zip belongs to the same authority as the nearest district
+    Consistent,
+    /// The zip belongs to another authority than the nearest district, so the notice may be sent to the wrong one
+    Mismatch,
+    /// The zip is not served by any known district
+    UnknownZip,
+    /// No district could be resolved from the coordinate
+    Unresolved,
+}
+
+#[derive(Debug, Clone)]
+pub struct DistrictCheck<'a> {
+    /// Nearest district to the coordinate
+    pub resolved: Option<DistrictMatch<'a>>,
+    /// District of the zip
+    pub zip_district: Option<&'a District>,
+    pub status: ZipCheck,
+}
+
+/// Resolves the responsible district of a coordinate offline, from the coordinates of known districts
+///
+/// Districts are considered the same authority if they share their email.
+pub struct DistrictResolver {
+    index: NoticeIndex<District>,
+    zips: BTreeMap<String, usize>,
+}
+
+fn same_authority(a: &District, b: &District) -> bool {
+    a.email.eq_ignore_ascii_case(&b.email)
+}
+
+impl DistrictResolver {
+    pub fn new<I: IntoIterator<Item = District>>(districts: I) -> Self {
+        let index = NoticeIndex::new(districts);
+        let zips = index
+            .items()
+            .iter()
+            .enumerate()
+            .map(|(position, district)| (district.zip.clone(), position))
+            .collect();
+        DistrictResolver { index, zips }
+    }
+
+    pub fn district_by_zip(&self, zip: &str) -> Option<&District> {
+        self.zips
+            .get(zip)
+            .map(|position| &self.index.items()[*position])
+    }
+
+    /// Nearest district to the given coordinate
+    ///
+    /// The confidence is the product of how much nearer the district is than the nearest district of another authority,
+    /// `1 - distance / competing distance`, and how near it is at all, `d / (d + distance)` with `d` being [DISTRICT_CONFIDENCE_DISTANCE].
+    /// The competing district is only looked for among the 16 nearest districts. If none of them belongs to another authority,
+    /// the first factor is 1, so the confidence is relative to those nearest candidates only.
+    pub fn resolve(&self, latitude: f64, longitude: f64) -> Option<DistrictMatch<'_>> {
+        let nearest = self.index.nearest(latitude, longitude, COMPETING_DISTRICTS);
+        let (district, distance) = *nearest.first()?;
+        let separation = match nearest
+            .iter()
+            .find(|(other, _)| !same_authority(district, other))
+        {
+            Some((_, competing_distance)) if *competing_distance > 0.0 => {
+                1.0 - distance / competing_distance
+            }
+            Some(_) => 0.0,
+            None => 1.0,
+        };
+        let proximity = DISTRICT_CONFIDENCE_DISTANCE / (DISTRICT_CONFIDENCE_DISTANCE + distance);
+        Some(DistrictMatch {
+            district,
+            distance,
+            confidence: separation * proximity,
+        })
+    }
+
+    /// Resolve the district of the given coordinate and compare it with the district of `zip`
+    pub fn check(&self, latitude: f64, longitude: f64, zip: &str) -> DistrictCheck<'_> {
+        let resolved = self.resolve(latitude, longitude);
+        let zip_district = self.district_by_zip(zip);
+        let status = match (&resolved, zip_district) {
+            (None, _) => ZipCheck::Unresolved,
+            (Some(_), None) => ZipCheck::UnknownZip,
+            (Some(resolved), Some(zip_district)) => {
+                if same_authority(resolved.district, zip_district) {
+                    ZipCheck::Consistent
+                } else {
+                    ZipCheck::Mismatch
+                }
+            }
+        };
+        DistrictCheck {
+            resolved,
+            zip_district,
+            status,
+        }
+    }
+
+    /// Check whether the zip of `notice` matches the district of its coordinates
+    pub fn check_notice(&self, notice: &Notice) -> DistrictCheck<'_> {
+        self.check(notice.latitude, notice.longitude, &notice.zip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::types::district::District;
+
+    use super::{DistrictResolver, ZipCheck};
+
+    fn district(name: &str, zip: &str, latitude: f64, longitude: f64) -> District {
+        District {
+            name: name.to_string(),
+            zip: zip.to_string(),
+            email: format!("ordnungsamt@{}.example.org", name.to_lowercase()),
+            prefixes: vec![],
+            latitude,
+            longitude,
+            aliases: None,
+            personal_email: false,
+            created_at: DateTime::parse_from_rfc3339("2023-09-18T15:30:27+02:00").unwrap(),
+            updated_at: DateTime::parse_from_rfc3339("2023-09-18T15:30:27+02:00").unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_district_resolver() {
+        let resolver = DistrictResolver::new([
+            district("Hamburg", "20095", 53.5503, 9.9930),
+            district("Hamburg", "22765", 53.5540, 9.9330),
+            district("Norderstedt", "22846", 53.7064, 9.9920),
+            district("Berlin", "10115", 52.5320, 13.3850),
+        ]);
+
+        let resolved = resolver.resolve(53.5520, 9.9400).unwrap();
+        assert_eq!(resolved.district.zip, "22765");
+        assert!(resolved.distance < 600.0);
+        assert!(resolved.confidence > 0.8);
+
+        // halfway between Hamburg and Norderstedt
+        let ambiguous = resolver.resolve(53.6283, 9.9925).unwrap();
+        assert!(ambiguous.confidence < 0.1);
+
+        assert_eq!(
+            resolver.check(53.5520, 9.9400, "20095").status,
+            ZipCheck::Consistent
+        );
+        let mismatch = resolver.check(53.7000, 9.9900, "20095");
+        assert_eq!(mismatch.status, ZipCheck::Mismatch);
+        assert_eq!(mismatch.resolved.unwrap().district.name, "Norderstedt");
+        assert_eq!(
+            resolver.check(53.5520, 9.9400, "99999").status,
+            ZipCheck::UnknownZip
+        );
+        assert_eq!(
+            DistrictResolver::new([])
+                .check(53.5520, 9.9400, "20095")
+                .status,
+            ZipCheck::Unresolved
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use rstar::{primitives::GeomWithData, RTree, AABB};
+
+use super::{haversine_distance, BoundingBox, Located, EARTH_RADIUS_METERS};
+
+/// Radius the search for the nearest notices starts with, in meters
+const NEAREST_START_RADIUS: f64 = 100.0;
+
+/// Points in the tree are `[longitude, latitude]`, with the position of the item in `items`
+type IndexedPoint = GeomWithData<[f64; 2], usize>;
+
+/// R-tree over the coordinates of notices, export notices or anything else [Located]
+///
+/// Items without coordinates are kept aside and available through [NoticeIndex::unlocated].
+pub struct NoticeIndex<T: Located> {
+    items: Vec<T>,
+    tree: RTree<IndexedPoint>,
+    unlocated: Vec<usize>,
+}
+
+impl<T: Located> NoticeIndex<T> {
+    pub fn new<I: IntoIterator<Item = T>>(items: I) -> Self {
+        let items: Vec<T> = items.into_iter().collect();
+        let mut points = vec![];
+        let mut unlocated = vec![];
+        for (position, item) in items.iter().enumerate() {
+            match item.coordinates() {
+                Some((latitude, longitude)) if latitude.is_finite() && longitude.is_finite() => {
+                    points.push(IndexedPoint::new([longitude, latitude], position))
+                }
+                _ => unlocated.push(position),
+            }
+        }
+        NoticeIndex {
+            items,
+            tree: RTree::bulk_load(points),
+            unlocated,
+        }
+    }
+
+    /// Number of items in the index, unlocated ones included
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Items without (valid) coordinates, which never match a spatial query
+    pub fn unlocated(&self) -> Vec<&T> {
+        self.unlocated
+            .iter()
+            .map(|position| &self.items[*position])
+            .collect()
+    }
+
+    pub fn within_bounding_box(&self, bounding_box: &BoundingBox) -> Vec<&T> {
+        let envelope = AABB::from_corners(
+            [bounding_box.min_longitude, bounding_box.min_latitude],
+            [bounding_box.max_longitude, bounding_box.max_latitude],
+        );
+        self.tree
+            .locate_in_envelope(&envelope)
+            .map(|point| &self.items[point.data])
+            .collect()
+    }
+
+    /// Items within `radius` meters of the given point with their distance, nearest first
+    pub fn within_radius(&self, latitude: f64, longitude: f64, radius: f64) -> Vec<(&T, f64)> {
+        let bounding_box = BoundingBox::around(latitude, longitude, radius);
+        let envelope = AABB::from_corners(
+            [bounding_box.min_longitude, bounding_box.min_latitude],
+            [bounding_box.max_longitude, bounding_box.max_latitude],
+        );
+        let mut results: Vec<(&T, f64)> = self
+            .tree
+            .locate_in_envelope(&envelope)
+            .filter_map(|point| {
+                let [point_longitude, point_latitude] = *point.geom();
+                let distance =
+                    haversine_distance(latitude, longitude, point_latitude, point_longitude);
+                if distance <= radius {
+                    Some((&self.items[point.data], distance))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        results.sort_by(|a, b| a.1.total_cmp(&b.1));
+        results
+    }
+
+    /// The `count` items nearest to the given point with their distance, nearest first
+    ///
+    /// Searches within a radius that is doubled until enough items are found,
+    /// so distances are great-circle distances rather than distances in degrees.
+    pub fn nearest(&self, latitude: f64, longitude: f64, count: usize) -> Vec<(&T, f64)> {
+        let count = count.min(self.tree.size());
+        if count == 0 {
+            return vec![];
+        }
+        let max_radius = std::f64::consts::PI * EARTH_RADIUS_METERS;
+        let mut radius = NEAREST_START_RADIUS;
+        loop {
+            let mut results = self.within_radius(latitude, longitude, radius);
+            if results.len() >= count || radius >= max_radius {
+                results.truncate(count);
+                return results;
+            }
+            radius *= 2.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::{geo::BoundingBox, types::export::ExportNotice};
+
+    use super::NoticeIndex;
+
+    fn export_notice(street: &str, coordinates: Option<(f64, f64)>) -> ExportNotice {
+        ExportNotice {
+            start_date: DateTime::parse_from_rfc3339("2024-03-01T08:00:00+01:00").unwrap(),
+            end_date: DateTime::parse_from_rfc3339("2024-03-01T08:10:00+01:00").unwrap(),
+            tbnr: "112454".to_string(),
+            street: street.to_string(),
+            city: "Hamburg".to_string(),
+            zip: "20095".to_string(),
+            latitude: coordinates.map(|val| val.0),
+            longitude: coordinates.map(|val| val.1),
+        }
+    }
+
+    #[test]
+    fn test_notice_index() {
+        let index = NoticeIndex::new(vec![
+            // Rathausmarkt
+            export_notice("Rathausmarkt 1", Some((53.5503, 9.9925))),
+            // about 110 m north
+            export_notice("Mönckebergstraße 2", Some((53.5513, 9.9925))),
+            // about 650 m east
+            export_notice("Steintorplatz 1", Some((53.5503, 10.0023))),
+            // Berlin
+            export_notice("Unter den Linden 1", Some((52.5170, 13.3889))),
+            export_notice("Unbekannt", None),
+        ]);
+        assert_eq!(index.len(), 5);
+        assert_eq!(index.unlocated()[0].street, "Unbekannt");
+
+        let nearby = index.within_radius(53.5503, 9.9925, 200.0);
+        assert_eq!(nearby.len(), 2);
+        assert_eq!(nearby[0].0.street, "Rathausmarkt 1");
+        assert!((nearby[1].1 - 111.2).abs() < 1.0);
+
+        let hamburg = index.within_bounding_box(&BoundingBox {
+            min_latitude: 53.4,
+            min_longitude: 9.7,
+            max_latitude: 53.7,
+            max_longitude: 10.3,
+        });
+        assert_eq!(hamburg.len(), 3);
+
+        let nearest = index.nearest(52.52, 13.40, 2);
+        assert_eq!(nearest[0].0.street, "Unter den Linden 1");
+        assert_eq!(nearest[1].0.street, "Steintorplatz 1");
+        assert_eq!(index.nearest(52.52, 13.40, 10).len(), 4);
+    }
+}
@@ -0,0 +1,91 @@
+pub mod cluster;
+pub mod district;
+pub mod index;
+
+use crate::types::{export::ExportNotice, notice::Notice};
+
+/// Mean earth radius in meters
+pub const EARTH_RADIUS_METERS: f64 = 6_371_008.8;
+
+/// Values with a position on the map
+pub trait Located {
+    /// Latitude and longitude in degrees, `None` if unknown
+    fn coordinates(&self) -> Option<(f64, f64)>;
+}
+
+impl<T: Located> Located for &T {
+    fn coordinates(&self) -> Option<(f64, f64)> {
+        (*self).coordinates()
+    }
+}
+
+/// Item with its position in a slice, to map results of a [NoticeIndex](index::NoticeIndex) back to positions
+pub(crate) struct Positioned<'a, T> {
+    pub position: usize,
+    pub item: &'a T,
+}
+
+impl<T: Located> Located for Positioned<'_, T> {
+    fn coordinates(&self) -> Option<(f64, f64)> {
+        self.item.coordinates()
+    }
+}
+
+impl Located for Notice {
+    fn coordinates(&self) -> Option<(f64, f64)> {
+        Some((self.latitude, self.longitude))
+    }
+}
+
+impl Located for ExportNotice {
+    fn coordinates(&self) -> Option<(f64, f64)> {
+        match (self.latitude, self.longitude) {
+            (Some(latitude), Some(longitude)) => Some((latitude, longitude)),
+            _ => None,
+        }
+    }
+}
+
+/// Area between two latitudes and two longitudes, in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_latitude: f64,
+    pub min_longitude: f64,
+    pub max_latitude: f64,
+    pub max_longitude: f64,
+}
+
+impl BoundingBox {
+    /// Smallest box containing every point within `radius` meters of the given point
+    pub fn around(latitude: f64, longitude: f64, radius: f64) -> Self {
+        let latitude_delta = (radius / EARTH_RADIUS_METERS).to_degrees();
+        let longitude_delta = latitude_delta / latitude.to_radians().cos().max(1e-6);
+        BoundingBox {
+            min_latitude: (latitude - latitude_delta).max(-90.0),
+            min_longitude: (longitude - longitude_delta).max(-180.0),
+            max_latitude: (latitude + latitude_delta).min(90.0),
+            max_longitude: (longitude + longitude_delta).min(180.0),
+        }
+    }
+
+    pub fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        (self.min_latitude..=self.max_latitude).contains(&latitude)
+            && (self.min_longitude..=self.max_longitude).contains(&longitude)
+    }
+}
+
+/// Great-circle distance in meters between two points given in degrees
+pub fn haversine_distance(
+    latitude_a: f64,
+    longitude_a: f64,
+    latitude_b: f64,
+    longitude_b: f64,
+) -> f64 {
+    let latitude_delta = (latitude_b - latitude_a).to_radians();
+    let longitude_delta = (longitude_b - longitude_a).to_radians();
+    let a = (latitude_delta / 2.0).sin().powi(2)
+        + latitude_a.to_radians().cos()
+            * latitude_b.to_radians().cos()
+            * (longitude_delta / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_METERS * a.sqrt().min(1.0).asin()
+}
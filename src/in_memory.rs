@@ -0,0 +1,359 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset};
+use reqwest::StatusCode;
+
+use crate::{
+    api::{
+        error::{ApiError, DownloadError},
+        export::unzip_weg_li_notices_archive,
+        wegli_api::WegLiApi,
+    },
+    export::{reader::read_export_csv, writer::write_export_zip},
+    types::{
+        charge::Charge,
+        district::District,
+        download::DownloadOptions,
+        export::{Export, ExportDownload, ExportNotice, ExportType},
+        notice::{Notice, NoticePhotosJson},
+    },
+};
+
+#[derive(Debug, Clone)]
+struct InMemoryExport {
+    export: Export,
+    public: bool,
+    notices: Vec<ExportNotice>,
+}
+
+/// [WegLiApi] implementation serving seeded data from memory, for tests without HTTP
+///
+/// Unknown tokens, tbnrs and zip codes fail with a 404 [ApiError::UnexpectedStatusCode] like the API does.
+/// Exports are written as zip archives in weg.li's export format when downloaded.
+#[derive(Debug, Default)]
+pub struct InMemoryWegLi {
+    notices: RwLock<BTreeMap<String, Notice>>,
+    charges: RwLock<BTreeMap<String, Charge>>,
+    districts: RwLock<BTreeMap<String, District>>,
+    exports: RwLock<Vec<InMemoryExport>>,
+    photos: RwLock<BTreeMap<String, Vec<u8>>>,
+}
+
+fn not_found() -> ApiError {
+    ApiError::UnexpectedStatusCode(StatusCode::NOT_FOUND)
+}
+
+impl InMemoryWegLi {
+    pub fn new() -> Self {
+        InMemoryWegLi::default()
+    }
+
+    pub fn with_notices<I: IntoIterator<Item = Notice>>(self, notices: I) -> Self {
+        for notice in notices {
+            self.insert_notice(notice);
+        }
+        self
+    }
+
+    pub fn with_charges<I: IntoIterator<Item = Charge>>(self, charges: I) -> Self {
+        for charge in charges {
+            self.insert_charge(charge);
+        }
+        self
+    }
+
+    pub fn with_districts<I: IntoIterator<Item = District>>(self, districts: I) -> Self {
+        for district in districts {
+            self.insert_district(district);
+        }
+        self
+    }
+
+    /// Add an export listed as public or user export, downloaded as `filename`
+    pub fn with_export(
+        self,
+        filename: &str,
+        created_at: DateTime<FixedOffset>,
+        public: bool,
+        notices: Vec<ExportNotice>,
+    ) -> Self {
+        self.exports.write().unwrap().push(InMemoryExport {
+            export: Export {
+                export_type: ExportType::NOTICES,
+                file_extension: "csv".to_string(),
+                created_at,
+                download: ExportDownload {
+                    filename: filename.to_string(),
+                    url: format!("memory://exports/{}", filename),
+                },
+            },
+            public,
+            notices,
+        });
+        self
+    }
+
+    /// Add an export with the rows of the export CSV at `path`, downloaded as a zip named after the CSV
+    pub fn with_export_csv(
+        self,
+        path: &Path,
+        created_at: DateTime<FixedOffset>,
+        public: bool,
+    ) -> Result<Self, anyhow::Error> {
+        let notices = read_export_csv(path)?;
+        let filename = match path.file_stem().and_then(|val| val.to_str()) {
+            None => return Err(anyhow!("invalid export path: {:?}", path)),
+            Some(val) => format!("{}.zip", val),
+        };
+        Ok(self.with_export(&filename, created_at, public, notices))
+    }
+
+    /// Serve `content` for downloads of the photo at `url`
+    pub fn with_photo(self, url: &str, content: Vec<u8>) -> Self {
+        self.photos
+            .write()
+            .unwrap()
+            .insert(url.to_string(), content);
+        self
+    }
+
+    /// Add or replace a notice, e.g. to simulate a status change between two polls
+    pub fn insert_notice(&self, notice: Notice) {
+        self.notices
+            .write()
+            .unwrap()
+            .insert(notice.token.clone(), notice);
+    }
+
+    pub fn remove_notice(&self, notice_token: &str) -> Option<Notice> {
+        self.notices.write().unwrap().remove(notice_token)
+    }
+
+    pub fn insert_charge(&self, charge: Charge) {
+        self.charges
+            .write()
+            .unwrap()
+            .insert(charge.tbnr.clone(), charge);
+    }
+
+    pub fn insert_district(&self, district: District) {
+        self.districts
+            .write()
+            .unwrap()
+            .insert(district.zip.clone(), district);
+    }
+
+    fn exports(&self, public: bool) -> Vec<Export> {
+        self.exports
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|export| export.public == public)
+            .map(|export| export.export.clone())
+            .collect()
+    }
+}
+
+#[async_trait]
+impl WegLiApi for InMemoryWegLi {
+    async fn get_notice(&self, notice_token: &str) -> Result<Notice, ApiError> {
+        match self.notices.read().unwrap().get(notice_token) {
+            None => Err(not_found()),
+            Some(notice) => Ok(notice.clone()),
+        }
+    }
+    async fn get_notices(&self) -> Result<Vec<Notice>, ApiError> {
+        Ok(self.notices.read().unwrap().values().cloned().collect())
+    }
+    async fn get_charge(&self, tbnr: &str) -> Result<Charge, ApiError> {
+        match self.charges.read().unwrap().get(tbnr) {
+            None => Err(not_found()),
+            Some(charge) => Ok(charge.clone()),
+        }
+    }
+    async fn get_charges(&self) -> Result<Vec<Charge>, ApiError> {
+        Ok(self.charges.read().unwrap().values().cloned().collect())
+    }
+    async fn get_district(&self, zip: &str) -> Result<District, ApiError> {
+        match self.districts.read().unwrap().get(zip) {
+            None => Err(not_found()),
+            Some(district) => Ok(district.clone()),
+        }
+    }
+    async fn get_districts(&self) -> Result<Vec<District>, ApiError> {
+        Ok(self.districts.read().unwrap().values().cloned().collect())
+    }
+    async fn get_user_exports(&self) -> Result<Vec<Export>, ApiError> {
+        Ok(self.exports(false))
+    }
+    async fn get_public_exports(&self) -> Result<Vec<Export>, ApiError> {
+        Ok(self.exports(true))
+    }
+    async fn download_export(
+        &self,
+        export: &Export,
+        path: &Path,
+        unzip: bool,
+        _download_options: &DownloadOptions,
+    ) -> Result<PathBuf, anyhow::Error> {
+        let notices = match self
+            .exports
+            .read()
+            .unwrap()
+            .iter()
+            .find(|val| val.export.download.url == export.download.url)
+        {
+            None => return Err(anyhow!("unknown export: {}", export.download.url)),
+            Some(val) => val.notices.clone(),
+        };
+        let zip_path = path.join(&export.download.filename);
+        let csv_file_name = Path::new(&export.download.filename).with_extension("csv");
+        write_export_zip(&zip_path, &csv_file_name.to_string_lossy(), &notices)?;
+        if unzip {
+            return unzip_weg_li_notices_archive(&zip_path, path);
+        }
+        Ok(zip_path)
+    }
+    async fn download_notice_photo(
+        &self,
+        photo: &NoticePhotosJson,
+        path: &Path,
+        _download_options: &DownloadOptions,
+    ) -> Result<PathBuf, DownloadError> {
+        let content = match self.photos.read().unwrap().get(&photo.url) {
+            None => {
+                return Err(DownloadError::Io(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("unknown photo: {}", photo.url),
+                )))
+            }
+            Some(val) => val.clone(),
+        };
+        let photo_path = path.join(&photo.filename);
+        match fs::write(&photo_path, content) {
+            Err(error) => Err(DownloadError::Io(error)),
+            Ok(_) => Ok(photo_path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::DateTime;
+
+    use crate::{
+        api::{error::ApiError, wegli_api::WegLiApi},
+        export::reader::read_export_csv,
+        types::{
+            export::ExportNotice,
+            notice::{Notice, NoticeJson, NoticeStatus},
+            request::BulkOptions,
+        },
+        watcher::{NoticeWatcher, DEFAULT_NOTICE_WATCHER_SETTINGS},
+    };
+
+    use super::InMemoryWegLi;
+
+    fn notice(token: &str, status: &str) -> Notice {
+        let json = format!(
+            r#"{{
+                "token": "{}",
+                "status": "{}",
+                "street": "Hauptstraße 1",
+                "city": "Metropolis",
+                "zip": "12345",
+                "latitude": 52.5,
+                "longitude": 13.4,
+                "registration": "B AB 123",
+                "color": "silver",
+                "brand": "Opel",
+                "charge": {{
+                    "tbnr": "112454",
+                    "description": "Sie parkten verbotswidrig auf dem Gehweg.",
+                    "fine": "55.0",
+                    "bkat": "§ 12 Abs. 4, § 49 StVO; § 24 Abs. 1, 3 Nr. 5 StVG; 52a BKat",
+                    "penalty": null,
+                    "fap": null,
+                    "points": 0,
+                    "valid_from": "2021-11-09T00:00:00.000+01:00",
+                    "valid_to": null,
+                    "implementation": null,
+                    "classification": 5,
+                    "variant_table_id": 712031,
+                    "rule_id": 272,
+                    "table_id": null,
+                    "required_refinements": "00000000000000000000000000000000",
+                    "number_required_refinements": 0,
+                    "max_fine": "0.0",
+                    "created_at": "2023-09-18T15:30:27.417+02:00",
+                    "updated_at": "2023-09-18T15:30:27.417+02:00"
+                }},
+                "tbnr": "112454",
+                "start_date": "2023-10-25T09:23:00.000+01:00",
+                "end_date": "2023-10-25T09:41:00.000+01:00",
+                "note": null,
+                "photos": [],
+                "created_at": "2023-10-25T09:23:30.830+01:00",
+                "updated_at": "2023-10-25T09:41:42.638+01:00",
+                "sent_at": "2023-10-25T09:42:32.612+01:00",
+                "vehicle_empty": true,
+                "hazard_lights": false,
+                "expired_tuv": false,
+                "expired_eco": false,
+                "over_2_8_tons": false
+            }}"#,
+            token, status
+        );
+        Notice::try_from(&serde_json::from_str::<NoticeJson>(&json).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_wegli() {
+        let export_notice = ExportNotice::from(&notice("abc", "open"));
+        let api = InMemoryWegLi::new()
+            .with_notices([notice("abc", "open")])
+            .with_export(
+                "notices-47.zip",
+                DateTime::parse_from_rfc3339("2022-11-21T03:02:19+01:00").unwrap(),
+                true,
+                vec![export_notice.clone()],
+            );
+
+        let results = api
+            .get_notices_by_token(
+                vec!["abc".to_string(), "xyz".to_string()],
+                &BulkOptions::default(),
+            )
+            .await;
+        assert_eq!(results[0].1.as_ref().unwrap().token, "abc");
+        assert!(matches!(
+            results[1].1,
+            Err(ApiError::UnexpectedStatusCode(status)) if status == 404
+        ));
+
+        // business logic depending on `&dyn WegLiApi` runs against the in-memory data
+        let mut watcher = NoticeWatcher::new(DEFAULT_NOTICE_WATCHER_SETTINGS).unwrap();
+        assert!(watcher.poll(&api).await.unwrap().is_empty());
+        api.insert_notice(notice("abc", "shared"));
+        let changes = watcher.poll(&api).await.unwrap();
+        assert_eq!(changes[0].previous, Some(NoticeStatus::OPEN));
+        assert_eq!(changes[0].current, NoticeStatus::SHARED);
+
+        let dir = std::env::temp_dir().join("weg_li_api_test_in_memory_wegli");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let csv_path = api.download_latest_export(&dir, true, true).await.unwrap();
+        assert_eq!(read_export_csv(&csv_path).unwrap(), vec![export_notice]);
+        assert!(api.download_latest_export(&dir, false, true).await.is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,12 +1,36 @@
 #![cfg_attr(not(doctest), doc = include_str!("../README.md"))]
 
+pub mod anonymize;
 pub mod api;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod dedup;
+pub mod directory;
+pub mod export;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+pub mod geo;
+pub mod in_memory;
+pub mod poller;
+mod polling;
+pub mod registration;
+pub mod report;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+mod state_file;
+pub mod statistics;
+pub mod sync;
 pub mod types;
+pub mod watcher;
 
+pub use api::wegli_api::WegLiApi;
 pub use api::WegLiApiClient;
 pub use types::charge::{Charge, ChargeJson};
 pub use types::district::{District, DistrictJson};
 pub use types::export::{
-    Export, ExportDownload, ExportJson, ExportNotice, ExportNoticeCsv, ExportType,
+    Export, ExportDownload, ExportJson, ExportNotice, ExportNoticeCsv, ExportSelector, ExportType,
 };
 pub use types::notice::{Notice, NoticeJson, NoticePhotosJson, NoticeStatus};
+pub use types::request::{
+    BulkOptions, CircuitBreakerSettings, EndpointGroup, RateLimitSettings, RateLimiterStats,
+};
@@ -0,0 +1,451 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset};
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    api::wegli_api::WegLiApi,
+    export::reader::read_export_csv,
+    polling::{run_polls, Poll},
+    state_file::{load_state, save_state},
+    types::{
+        download::DownloadOptions,
+        export::{Export, ExportNotice},
+    },
+};
+
+/// Single path component named after the API provided `filename` of an export, with its extension replaced by `extension`
+///
+/// Only the last component of `filename` is used, so the result can't point outside the directory it is joined onto.
+fn export_file_name(filename: &str, extension: &str) -> Result<PathBuf, anyhow::Error> {
+    let name = match Path::new(filename).file_name() {
+        None => return Err(anyhow!("invalid export filename: {:?}", filename)),
+        Some(val) => Path::new(val).with_extension(extension),
+    };
+    match name.components().collect::<Vec<Component>>()[..] {
+        [Component::Normal(_)] => Ok(name),
+        _ => Err(anyhow!("invalid export filename: {:?}", filename)),
+    }
+}
+
+/// A downloaded export whose CSV was extracted and parsed successfully
+#[derive(Debug, Clone)]
+pub struct IngestedExport {
+    pub export: Export,
+    /// Path of the extracted CSV file
+    pub csv_path: PathBuf,
+    pub notices: Vec<ExportNotice>,
+}
+
+/// Destination of new exports found by an [ExportPoller]
+///
+/// Delivery is at least once: if any sink of a poller fails, the export is offered again to all sinks on the next poll,
+/// including those that already took it over. Sinks should therefore be idempotent, as the built-in ones are.
+#[async_trait]
+pub trait ExportSink: Send {
+    /// Take over a new export. If this fails, the export is offered again on the next poll.
+    async fn ingest(&mut self, export: &IngestedExport) -> Result<(), anyhow::Error>;
+}
+
+/// Copies the CSV of every new export into a directory, named after the export's download filename
+pub struct DirectorySink {
+    pub path: PathBuf,
+}
+
+#[async_trait]
+impl ExportSink for DirectorySink {
+    async fn ingest(&mut self, export: &IngestedExport) -> Result<(), anyhow::Error> {
+        if let Err(error) = fs::create_dir_all(&self.path) {
+            return Err(anyhow!(error));
+        }
+        let file_name = export_file_name(&export.export.download.filename, "csv")?;
+        match fs::copy(&export.csv_path, self.path.join(file_name)) {
+            Err(error) => Err(anyhow!(error)),
+            Ok(_) => Ok(()),
+        }
+    }
+}
+
+pub type ExportSinkCallback = Arc<
+    dyn for<'a> Fn(&'a IngestedExport) -> BoxFuture<'a, Result<(), anyhow::Error>> + Send + Sync,
+>;
+
+/// Hands every new export to an async callback
+///
+/// The callback borrows the export instead of taking a copy of its notices, so it returns a boxed future,
+/// e.g. `CallbackSink::new(|export| Box::pin(async move { ... }))`.
+pub struct CallbackSink {
+    callback: ExportSinkCallback,
+}
+
+impl CallbackSink {
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: for<'a> Fn(&'a IngestedExport) -> BoxFuture<'a, Result<(), anyhow::Error>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        CallbackSink {
+            callback: Arc::new(callback),
+        }
+    }
+}
+
+#[async_trait]
+impl ExportSink for CallbackSink {
+    async fn ingest(&mut self, export: &IngestedExport) -> Result<(), anyhow::Error> {
+        (self.callback)(export).await
+    }
+}
+
+/// Loads every new export into SQLite, keyed by the export's download filename
+#[cfg(feature = "sqlite")]
+pub struct SqliteSink {
+    pub importer: crate::sqlite::SqliteImporter,
+}
+
+#[cfg(feature = "sqlite")]
+#[async_trait]
+impl ExportSink for SqliteSink {
+    async fn ingest(&mut self, export: &IngestedExport) -> Result<(), anyhow::Error> {
+        self.importer
+            .load_export_notices(&export.export.download.filename, &export.notices)
+            .map(|_| ())
+    }
+}
+
+pub type ExportPollerErrorHandler = Arc<dyn Fn(&anyhow::Error) + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub struct ExportPollerSettings {
+    /// Time between two polls
+    pub poll_interval: Duration,
+    /// Poll the publicly available exports if `true`, otherwise the authenticated user's ones
+    pub public: bool,
+    /// File to keep the creation time of the last ingested export in across restarts
+    pub state_path: Option<PathBuf>,
+    /// Ingest only the latest export when there is no saved state yet, instead of every export listed
+    pub start_from_latest: bool,
+}
+
+/// weg.li creates the public export weekly, polling hourly picks it up in time without hammering the API
+pub const DEFAULT_EXPORT_POLLER_SETTINGS: ExportPollerSettings = ExportPollerSettings {
+    poll_interval: Duration::from_secs(3600),
+    public: true,
+    state_path: None,
+    start_from_latest: false,
+};
+
+/// Creation time of the last ingested export, as persisted in the state file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExportPollerState {
+    pub last_created_at: Option<DateTime<FixedOffset>>,
+}
+
+impl ExportPollerState {
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        load_state(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        save_state(self, path)
+    }
+}
+
+/// Checks the list of exports on a schedule and ingests every new export into its sinks
+///
+/// An export is new if it was created after the last ingested one. Without a saved state every listed export is new,
+/// or only the latest one with `start_from_latest`. New exports are ingested oldest first,
+/// each is downloaded into its own directory below `download_dir`, extracted within the client's
+/// `unzip_limits` and parsed before it is handed to the sinks, see [ExportSink] for the delivery guarantee.
+pub struct ExportPoller {
+    download_dir: PathBuf,
+    settings: ExportPollerSettings,
+    state: ExportPollerState,
+    sinks: Vec<Box<dyn ExportSink>>,
+    error_handler: Option<ExportPollerErrorHandler>,
+}
+
+impl ExportPoller {
+    /// Create a poller, restoring the creation time of the last ingested export from `settings.state_path` if the file exists
+    pub fn new(download_dir: &Path, settings: ExportPollerSettings) -> Result<Self, anyhow::Error> {
+        let state = match &settings.state_path {
+            None => ExportPollerState::default(),
+            Some(path) => ExportPollerState::load(path)?,
+        };
+        Ok(ExportPoller {
+            download_dir: download_dir.to_path_buf(),
+            settings,
+            state,
+            sinks: vec![],
+            error_handler: None,
+        })
+    }
+
+    pub fn state(&self) -> &ExportPollerState {
+        &self.state
+    }
+
+    pub fn add_sink<S: ExportSink + 'static>(&mut self, sink: S) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    /// Call `handler` when a poll fails. The poller keeps polling in the next interval.
+    pub fn on_error<F>(&mut self, handler: F)
+    where
+        F: Fn(&anyhow::Error) + Send + Sync + 'static,
+    {
+        self.error_handler = Some(Arc::new(handler));
+    }
+
+    /// Poll once and ingest every export created after the last ingested one, oldest first
+    ///
+    /// The state is saved after each export, so a failing export doesn't cause the ones before it to be ingested again.
+    /// Returns the ingested exports, oldest first.
+    pub async fn poll(&mut self, client: &dyn WegLiApi) -> Result<Vec<Export>, anyhow::Error> {
+        let exports = if self.settings.public {
+            client.get_public_exports().await
+        } else {
+            client.get_user_exports().await
+        };
+        let mut exports: Vec<Export> = match exports {
+            Err(error) => return Err(anyhow!(error)),
+            Ok(val) => val,
+        };
+        if let Some(last_created_at) = self.state.last_created_at {
+            exports.retain(|export| export.created_at > last_created_at);
+        }
+        exports.sort_by_key(|export| export.created_at);
+        if self.state.last_created_at.is_none() && self.settings.start_from_latest {
+            exports.drain(..exports.len().saturating_sub(1));
+        }
+
+        let mut ingested_exports = vec![];
+        for export in exports {
+            self.ingest(client, &export).await?;
+            self.state.last_created_at = Some(export.created_at);
+            if let Some(path) = &self.settings.state_path {
+                self.state.save(path)?;
+            }
+            ingested_exports.push(export);
+        }
+        Ok(ingested_exports)
+    }
+
+    async fn ingest(
+        &mut self,
+        client: &dyn WegLiApi,
+        export: &Export,
+    ) -> Result<(), anyhow::Error> {
+        let dir = self
+            .download_dir
+            .join(export_file_name(&export.download.filename, "")?);
+        if dir.parent() != Some(self.download_dir.as_path()) {
+            return Err(anyhow!("{:?} is not inside {:?}", dir, self.download_dir));
+        }
+        // files of a previous, failed attempt would make the extraction fail
+        if dir.exists() {
+            if let Err(error) = fs::remove_dir_all(&dir) {
+                return Err(anyhow!(error));
+            }
+        }
+        if let Err(error) = fs::create_dir_all(&dir) {
+            return Err(anyhow!(error));
+        }
+        let csv_path = client
+            .download_export(export, &dir, true, &DownloadOptions::default())
+            .await?;
+        let ingested = IngestedExport {
+            notices: read_export_csv(&csv_path)?,
+            export: export.clone(),
+            csv_path,
+        };
+        for sink in self.sinks.iter_mut() {
+            sink.ingest(&ingested).await?;
+        }
+        Ok(())
+    }
+
+    /// Poll every `poll_interval` until `cancellation_token` is cancelled
+    pub async fn run(&mut self, client: &dyn WegLiApi, cancellation_token: CancellationToken) {
+        let poll_interval = self.settings.poll_interval;
+        run_polls(self, client, poll_interval, cancellation_token).await
+    }
+}
+
+#[async_trait]
+impl Poll for ExportPoller {
+    async fn poll_once(&mut self, client: &dyn WegLiApi) -> Result<(), anyhow::Error> {
+        self.poll(client).await.map(|_| ())
+    }
+
+    fn poll_failed(&self, error: &anyhow::Error) {
+        if let Some(error_handler) = &self.error_handler {
+            error_handler(error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        path::Path,
+        sync::{Arc, Mutex},
+    };
+
+    use chrono::DateTime;
+
+    use crate::{
+        api::WegLiApiClient, export::writer::write_export_zip, types::export::ExportNotice,
+    };
+
+    use super::{
+        export_file_name, CallbackSink, DirectorySink, ExportPoller, ExportPollerSettings,
+        ExportPollerState, DEFAULT_EXPORT_POLLER_SETTINGS,
+    };
+
+    #[test]
+    fn test_export_file_name() {
+        assert_eq!(
+            export_file_name("notices-47.zip", "csv").unwrap(),
+            Path::new("notices-47.csv")
+        );
+        assert_eq!(
+            export_file_name("../../notices-47.zip", "").unwrap(),
+            Path::new("notices-47")
+        );
+        for filename in ["", ".", "..", "/", "..zip", "/tmp/.."] {
+            assert!(export_file_name(filename, "").is_err(), "{:?}", filename);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_poller_poll() {
+        let mut server = mockito::Server::new_async().await;
+        let client = WegLiApiClient::new(&server.url(), &"any_api_key".to_string(), None).unwrap();
+        let dir = std::env::temp_dir().join("weg_li_api_test_export_poller_poll");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let notice = ExportNotice {
+            start_date: DateTime::parse_from_rfc3339("2023-10-25T09:23:00+02:00").unwrap(),
+            end_date: DateTime::parse_from_rfc3339("2023-10-25T09:41:00+02:00").unwrap(),
+            tbnr: "112454".to_string(),
+            street: "Hauptstraße 1".to_string(),
+            city: "Metropolis".to_string(),
+            zip: "12345".to_string(),
+            latitude: Some(52.5),
+            longitude: Some(13.4),
+        };
+        let zip_path = dir.join("source.zip");
+        write_export_zip(&zip_path, "notices.csv", [&notice]).unwrap();
+
+        let exports_mock = server
+            .mock("GET", "/exports/public")
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(format!(
+                r#"[{{
+                    "export_type": "notices",
+                    "file_extension": "csv",
+                    "created_at": "2022-11-21T03:02:19.396+01:00",
+                    "download": {{
+                        "filename": "notices-47.zip",
+                        "url": "{0}/blobs/notices-47.zip"
+                    }}
+                }},
+                {{
+                    "export_type": "notices",
+                    "file_extension": "csv",
+                    "created_at": "2022-11-14T03:01:58.056+01:00",
+                    "download": {{
+                        "filename": "notices-46.zip",
+                        "url": "{0}/blobs/notices-46.zip"
+                    }}
+                }}]"#,
+                server.url()
+            ))
+            .expect(3)
+            .create_async()
+            .await;
+        let blob_mock = server
+            .mock(
+                "GET",
+                mockito::Matcher::Regex("^/blobs/notices-4[67].zip$".to_string()),
+            )
+            .with_status(200)
+            .with_body(fs::read(&zip_path).unwrap())
+            .expect(3)
+            .create_async()
+            .await;
+
+        let state_path = dir.join("state.json");
+        let mut poller = ExportPoller::new(
+            &dir.join("downloads"),
+            ExportPollerSettings {
+                state_path: Some(state_path.clone()),
+                ..DEFAULT_EXPORT_POLLER_SETTINGS
+            },
+        )
+        .unwrap();
+        let filenames: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+        let filenames_clone = filenames.clone();
+        poller.add_sink(CallbackSink::new(move |export| {
+            let filenames = filenames_clone.clone();
+            Box::pin(async move {
+                assert_eq!(export.notices.len(), 1);
+                filenames
+                    .lock()
+                    .unwrap()
+                    .push(export.export.download.filename.clone());
+                Ok(())
+            })
+        }));
+        poller.add_sink(DirectorySink {
+            path: dir.join("sink"),
+        });
+
+        let ingested = poller.poll(&client).await.unwrap();
+        assert_eq!(ingested.len(), 2);
+        assert_eq!(
+            *filenames.lock().unwrap(),
+            vec!["notices-46.zip".to_string(), "notices-47.zip".to_string()]
+        );
+        assert!(dir.join("sink").join("notices-47.csv").exists());
+        assert!(poller.poll(&client).await.unwrap().is_empty());
+        assert_eq!(filenames.lock().unwrap().len(), 2);
+        assert_eq!(
+            ExportPollerState::load(&state_path)
+                .unwrap()
+                .last_created_at,
+            Some(ingested[1].created_at)
+        );
+
+        let mut poller = ExportPoller::new(
+            &dir.join("downloads"),
+            ExportPollerSettings {
+                start_from_latest: true,
+                ..DEFAULT_EXPORT_POLLER_SETTINGS
+            },
+        )
+        .unwrap();
+        let ingested = poller.poll(&client).await.unwrap();
+        assert_eq!(ingested.len(), 1);
+        assert_eq!(ingested[0].download.filename, "notices-47.zip");
+
+        exports_mock.assert_async().await;
+        blob_mock.assert_async().await;
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
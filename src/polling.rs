@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+
+use crate::api::wegli_api::WegLiApi;
+
+/// Something polling the API on a schedule, run by [run_polls]
+#[async_trait]
+pub(crate) trait Poll: Send {
+    async fn poll_once(&mut self, client: &dyn WegLiApi) -> Result<(), anyhow::Error>;
+
+    /// Called when a poll fails, polling continues in the next interval
+    fn poll_failed(&self, error: &anyhow::Error);
+}
+
+/// Poll every `poll_interval` until `cancellation_token` is cancelled, starting immediately
+///
+/// If a poll takes longer than the interval, the next one is delayed instead of catching up.
+pub(crate) async fn run_polls<P: Poll>(
+    target: &mut P,
+    client: &dyn WegLiApi,
+    poll_interval: Duration,
+    cancellation_token: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(poll_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            _ = cancellation_token.cancelled() => return,
+            _ = interval.tick() => {},
+        }
+        if let Err(error) = target.poll_once(client).await {
+            target.poll_failed(&error);
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use std::fmt;
+
+use anyhow::anyhow;
+
+use crate::{directory::DistrictDirectory, types::district::District};
+
+/// Maximum number of characters of prefix, letters and digits of a registration
+const MAX_REGISTRATION_LENGTH: usize = 8;
+
+/// Prefix of registrations of the German armed forces, followed by six digits
+const MILITARY_PREFIX: &str = "Y";
+
+/// Prefix of registrations of diplomatic vehicles, followed by the numbers of the mission and the vehicle
+const DIPLOMATIC_PREFIX: &str = "0";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegistrationKind {
+    /// Prefix, one or two letters and up to four digits
+    Standard,
+    /// Prefix and digits without letters, as used by authorities
+    Official,
+    /// "Y" followed by six digits
+    Military,
+    /// "0" followed by the numbers of the diplomatic mission and the vehicle
+    Diplomatic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegistrationSuffix {
+    /// "E" for electric vehicles
+    Electric,
+    /// "H" for historic vehicles
+    Historic,
+}
+
+impl fmt::Display for RegistrationSuffix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistrationSuffix::Electric => write!(f, "E"),
+            RegistrationSuffix::Historic => write!(f, "H"),
+        }
+    }
+}
+
+/// German vehicle registration, e.g. "HH AB 123E"
+///
+/// Parsed from upper or lower case with spaces, hyphens or colons as separators. The prefix has to be
+/// separated from the rest, as e.g. "HAB123" could be both "H AB 123" and "HA B 123".
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Registration {
+    /// Unterscheidungszeichen of the registering district, matching [District::prefixes]
+    pub prefix: String,
+    /// Identification letters, empty for official, military and diplomatic registrations
+    pub letters: String,
+    /// Identification number, for diplomatic registrations including the hyphen between mission and vehicle number
+    pub digits: String,
+    pub suffix: Option<RegistrationSuffix>,
+    pub kind: RegistrationKind,
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || c == '-' || c == ':'
+}
+
+impl std::str::FromStr for Registration {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.trim().to_uppercase();
+        let (prefix, rest) = match value.split_once(is_separator) {
+            None => return Err(anyhow!("'{}' has no separator after the prefix", s)),
+            Some(val) => val,
+        };
+        let rest = rest.trim_start_matches(is_separator);
+
+        if prefix == DIPLOMATIC_PREFIX {
+            let rest: String = rest.chars().filter(|c| !c.is_whitespace()).collect();
+            let mut parts = rest.split('-');
+            return match (parts.next(), parts.next(), parts.next()) {
+                (Some(mission), Some(vehicle), None)
+                    if !mission.is_empty()
+                        && !vehicle.is_empty()
+                        && mission.chars().all(|c| c.is_ascii_digit())
+                        && vehicle.chars().all(|c| c.is_ascii_digit()) =>
+                {
+                    Ok(Registration {
+                        prefix: prefix.to_string(),
+                        letters: String::new(),
+                        digits: rest,
+                        suffix: None,
+                        kind: RegistrationKind::Diplomatic,
+                    })
+                }
+                _ => Err(anyhow!("'{}' is not a valid diplomatic registration", s)),
+            };
+        }
+
+        let rest: String = rest.chars().filter(|c| !is_separator(*c)).collect();
+        if prefix.is_empty()
+            || prefix.chars().count() > 3
+            || !prefix.chars().all(char::is_alphabetic)
+        {
+            return Err(anyhow!("'{}' has an invalid prefix '{}'", s, prefix));
+        }
+        let suffix = match rest.chars().last() {
+            Some('E')
+                if rest
+                    .chars()
+                    .rev()
+                    .nth(1)
+                    .is_some_and(|c| c.is_ascii_digit()) =>
+            {
+                Some(RegistrationSuffix::Electric)
+            }
+            Some('H')
+                if rest
+                    .chars()
+                    .rev()
+                    .nth(1)
+                    .is_some_and(|c| c.is_ascii_digit()) =>
+            {
+                Some(RegistrationSuffix::Historic)
+            }
+            _ => None,
+        };
+        let rest = match suffix {
+            None => rest.as_str(),
+            Some(_) => &rest[..rest.len() - 1],
+        };
+        let letters: String = rest
+            .chars()
+            .take_while(|c| c.is_ascii_alphabetic())
+            .collect();
+        let digits = &rest[letters.len()..];
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(anyhow!("'{}' has no valid identification number", s));
+        }
+        if digits.starts_with('0') {
+            return Err(anyhow!("'{}' has a number with a leading zero", s));
+        }
+
+        let kind = if prefix == MILITARY_PREFIX {
+            if !letters.is_empty() || digits.len() != 6 {
+                return Err(anyhow!("'{}' is not a valid military registration", s));
+            }
+            RegistrationKind::Military
+        } else {
+            if letters.len() > 2
+                || digits.len() > 4
+                || prefix.chars().count() + letters.len() + digits.len() > MAX_REGISTRATION_LENGTH
+            {
+                return Err(anyhow!("'{}' is too long for a registration", s));
+            }
+            if letters.is_empty() {
+                RegistrationKind::Official
+            } else {
+                RegistrationKind::Standard
+            }
+        };
+        Ok(Registration {
+            prefix: prefix.to_string(),
+            letters,
+            digits: digits.to_string(),
+            suffix,
+            kind,
+        })
+    }
+}
+
+impl fmt::Display for Registration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            RegistrationKind::Standard => {
+                write!(f, "{} {} {}", self.prefix, self.letters, self.digits)?
+            }
+            RegistrationKind::Military => write!(
+                f,
+                "{} {} {}",
+                self.prefix,
+                &self.digits[..3],
+                &self.digits[3..]
+            )?,
+            RegistrationKind::Official | RegistrationKind::Diplomatic => {
+                write!(f, "{} {}", self.prefix, self.digits)?
+            }
+        }
+        if let Some(suffix) = self.suffix {
+            write!(f, "{}", suffix)?;
+        }
+        Ok(())
+    }
+}
+
+impl Registration {
+    /// Districts listing the prefix of this registration, i.e. where the vehicle may be registered
+    pub fn districts<'a>(&self, directory: &'a DistrictDirectory) -> Vec<&'a District> {
+        directory.districts_by_prefix(&self.prefix)
+    }
+
+    /// Whether the vehicle is registered in `district`, judging by its prefix
+    pub fn is_registered_in(&self, district: &District) -> bool {
+        district
+            .prefixes
+            .iter()
+            .any(|prefix| prefix.to_uppercase() == self.prefix)
+    }
+}
+
+/// Canonical form of `registration` to compare and group registrations: uppercase without separators, e.g. "HHAB123E" for "hh-ab 123 e"
+///
+/// Parsed and unparseable registrations get the same form, so spelling variants of a vehicle always match.
+/// Use the [Display](std::fmt::Display) of a parsed [Registration] to show a registration, e.g. "HH AB 123E".
+pub fn normalize_registration(registration: &str) -> String {
+    registration
+        .to_uppercase()
+        .chars()
+        .filter(|c| !is_separator(*c))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::{directory::DistrictDirectory, types::district::District};
+
+    use super::{normalize_registration, Registration, RegistrationKind, RegistrationSuffix};
+
+    fn district(name: &str, zip: &str, prefixes: &[&str]) -> District {
+        District {
+            name: name.to_string(),
+            zip: zip.to_string(),
+            email: format!("ordnungsamt@{}.example.org", zip),
+            prefixes: prefixes.iter().map(|val| val.to_string()).collect(),
+            latitude: 50.0,
+            longitude: 10.0,
+            aliases: None,
+            personal_email: false,
+            created_at: DateTime::parse_from_rfc3339("2023-09-18T15:30:27+02:00").unwrap(),
+            updated_at: DateTime::parse_from_rfc3339("2023-09-18T15:30:27+02:00").unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_registration() {
+        let registration: Registration = "hh-ab123 e".parse().unwrap();
+        assert_eq!(registration.prefix, "HH");
+        assert_eq!(registration.letters, "AB");
+        assert_eq!(registration.digits, "123");
+        assert_eq!(registration.suffix, Some(RegistrationSuffix::Electric));
+        assert_eq!(registration.kind, RegistrationKind::Standard);
+        assert_eq!(registration.to_string(), "HH AB 123E");
+
+        assert_eq!(
+            "TÜ H 1H".parse::<Registration>().unwrap().suffix,
+            Some(RegistrationSuffix::Historic)
+        );
+        assert_eq!(
+            "B 1234".parse::<Registration>().unwrap().kind,
+            RegistrationKind::Official
+        );
+        assert_eq!(
+            "Y-123 456".parse::<Registration>().unwrap().to_string(),
+            "Y 123 456"
+        );
+        assert_eq!(
+            "0 166-1".parse::<Registration>().unwrap().kind,
+            RegistrationKind::Diplomatic
+        );
+        for invalid in [
+            "HHAB123",
+            "HH AB 0123",
+            "HH ABC 1",
+            "EBE AB 12345",
+            "B AB",
+            "",
+        ] {
+            assert!(invalid.parse::<Registration>().is_err(), "{}", invalid);
+        }
+        assert_eq!(normalize_registration("b:xy 42"), "BXY42");
+        assert_eq!(
+            normalize_registration("HH AB 123"),
+            normalize_registration("HHAB123")
+        );
+        assert_eq!(normalize_registration("xx yyy 123"), "XXYYY123");
+
+        let directory = DistrictDirectory::new([
+            district("Hamburg", "20095", &["HH"]),
+            district("Pinneberg", "25421", &["PI"]),
+        ]);
+        let registration: Registration = "PI X 99".parse().unwrap();
+        assert_eq!(registration.districts(&directory)[0].name, "Pinneberg");
+        assert!(!registration.is_registered_in(directory.get("20095").unwrap()));
+    }
+}
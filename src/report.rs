@@ -0,0 +1,253 @@
+use std::{cmp::Reverse, collections::BTreeMap};
+
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+
+use crate::{
+    api::{error::ApiError, wegli_api::WegLiApi},
+    registration::normalize_registration,
+    types::notice::Notice,
+};
+
+/// Notices of one vehicle with the same charge
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChargeSummary {
+    pub tbnr: String,
+    pub description: String,
+    /// Fine of a single offense in Euros, as charged in the oldest notice
+    pub fine: f64,
+    /// Flensburg points of a single offense, as charged in the oldest notice
+    pub points: u8,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OffenseLocation {
+    pub token: String,
+    pub street: String,
+    pub zip: String,
+    pub city: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub start_date: DateTime<FixedOffset>,
+}
+
+/// Notices of one vehicle, to decide which cases to escalate to the authority
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VehicleReport {
+    /// Normalized registration, see [normalize_registration]
+    pub registration: String,
+    pub count: usize,
+    /// Charges by tbnr, most frequent first
+    pub charges: Vec<ChargeSummary>,
+    /// Locations of the notices, oldest first
+    pub locations: Vec<OffenseLocation>,
+    pub first_seen: DateTime<FixedOffset>,
+    pub last_seen: DateTime<FixedOffset>,
+    /// Sum of the fines of all notices in Euros
+    pub total_fine: f64,
+    /// Sum of the Flensburg points of all notices
+    pub total_points: u32,
+}
+
+fn vehicle_report(registration: String, mut notices: Vec<&Notice>) -> VehicleReport {
+    notices.sort_by_key(|notice| notice.start_date);
+    let mut charges: BTreeMap<&str, ChargeSummary> = BTreeMap::new();
+    for notice in notices.iter() {
+        charges
+            .entry(&notice.charge.tbnr)
+            .or_insert_with(|| ChargeSummary {
+                tbnr: notice.charge.tbnr.clone(),
+                description: notice.charge.description.clone(),
+                fine: notice.charge.fine,
+                points: notice.charge.points.unwrap_or(0),
+                count: 0,
+            })
+            .count += 1;
+    }
+    let mut charges: Vec<ChargeSummary> = charges.into_values().collect();
+    // stable sort keeps charges with the same count ordered by tbnr
+    charges.sort_by_key(|charge| Reverse(charge.count));
+
+    VehicleReport {
+        registration,
+        count: notices.len(),
+        // summed per notice, as the fine of a tbnr may have changed between notices
+        total_fine: notices.iter().map(|notice| notice.charge.fine).sum(),
+        total_points: notices
+            .iter()
+            .map(|notice| notice.charge.points.unwrap_or(0) as u32)
+            .sum(),
+        charges,
+        locations: notices
+            .iter()
+            .map(|notice| OffenseLocation {
+                token: notice.token.clone(),
+                street: notice.street.clone(),
+                zip: notice.zip.clone(),
+                city: notice.city.clone(),
+                latitude: notice.latitude,
+                longitude: notice.longitude,
+                start_date: notice.start_date,
+            })
+            .collect(),
+        first_seen: notices[0].start_date,
+        last_seen: notices[notices.len() - 1].start_date,
+    }
+}
+
+/// Group `notices` by normalized registration into reports of vehicles with at least `min_count` notices
+///
+/// Reports are sorted by number of notices, then by summed fines, largest first.
+/// Notices without a registration are left out, as they can't be attributed to a vehicle.
+pub fn repeat_offender_report(notices: &[Notice], min_count: usize) -> Vec<VehicleReport> {
+    let mut vehicles: BTreeMap<String, Vec<&Notice>> = BTreeMap::new();
+    for notice in notices {
+        let registration = normalize_registration(&notice.registration);
+        if registration.is_empty() {
+            continue;
+        }
+        vehicles.entry(registration).or_default().push(notice);
+    }
+    let mut reports: Vec<VehicleReport> = vehicles
+        .into_iter()
+        .filter(|(_, notices)| notices.len() >= min_count.max(1))
+        .map(|(registration, notices)| vehicle_report(registration, notices))
+        .collect();
+    reports.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| b.total_fine.total_cmp(&a.total_fine))
+    });
+    reports
+}
+
+/// Fetch the notices of the authenticated user and report vehicles with at least `min_count` notices
+pub async fn fetch_repeat_offender_report(
+    client: &dyn WegLiApi,
+    min_count: usize,
+) -> Result<Vec<VehicleReport>, ApiError> {
+    let notices = client.get_notices().await?;
+    Ok(repeat_offender_report(&notices, min_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        in_memory::InMemoryWegLi,
+        types::notice::{Notice, NoticeJson},
+    };
+
+    use super::fetch_repeat_offender_report;
+
+    fn notice(token: &str, registration: &str, tbnr: &str, fine: &str, start_date: &str) -> Notice {
+        let json = format!(
+            r#"{{
+                "token": "{}",
+                "status": "shared",
+                "street": "Lange Reihe 10",
+                "city": "Hamburg",
+                "zip": "20099",
+                "latitude": 53.556,
+                "longitude": 10.012,
+                "registration": "{}",
+                "color": "silver",
+                "brand": "Opel",
+                "charge": {{
+                    "tbnr": "{}",
+                    "description": "Sie parkten verbotswidrig.",
+                    "fine": "{}",
+                    "bkat": "§ 12 Abs. 4, § 49 StVO; § 24 Abs. 1, 3 Nr. 5 StVG",
+                    "penalty": null,
+                    "fap": null,
+                    "points": {},
+                    "valid_from": "2021-11-09T00:00:00.000+01:00",
+                    "valid_to": null,
+                    "implementation": null,
+                    "classification": 5,
+                    "variant_table_id": null,
+                    "rule_id": 272,
+                    "table_id": null,
+                    "required_refinements": "00000000000000000000000000000000",
+                    "number_required_refinements": 0,
+                    "max_fine": "0.0",
+                    "created_at": "2023-09-18T15:30:27.417+02:00",
+                    "updated_at": "2023-09-18T15:30:27.417+02:00"
+                }},
+                "tbnr": "{}",
+                "start_date": "{}",
+                "end_date": "{}",
+                "note": null,
+                "photos": [],
+                "created_at": "{}",
+                "updated_at": "{}",
+                "sent_at": "{}",
+                "vehicle_empty": true,
+                "hazard_lights": false,
+                "expired_tuv": false,
+                "expired_eco": false,
+                "over_2_8_tons": false
+            }}"#,
+            token,
+            registration,
+            tbnr,
+            fine,
+            if fine == "100.0" { 1 } else { 0 },
+            tbnr,
+            start_date,
+            start_date,
+            start_date,
+            start_date,
+            start_date
+        );
+        Notice::try_from(&serde_json::from_str::<NoticeJson>(&json).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_repeat_offender_report() {
+        let api = InMemoryWegLi::new().with_notices([
+            notice(
+                "a",
+                "HH AB 123",
+                "141322",
+                "55.0",
+                "2024-03-05T08:00:00+01:00",
+            ),
+            // the fine of the tbnr was raised after this notice
+            notice(
+                "b",
+                "hh-ab123",
+                "141322",
+                "40.0",
+                "2024-03-01T08:00:00+01:00",
+            ),
+            notice(
+                "c",
+                "HH AB 123",
+                "142678",
+                "100.0",
+                "2024-04-01T08:00:00+02:00",
+            ),
+            notice("d", "B XY 1", "112454", "55.0", "2024-03-01T08:00:00+01:00"),
+            notice("e", "B XY 1", "112454", "55.0", "2024-03-02T08:00:00+01:00"),
+            notice("f", "M C 7", "112454", "55.0", "2024-03-02T08:00:00+01:00"),
+            notice("g", "", "112454", "55.0", "2024-03-02T08:00:00+01:00"),
+            notice("h", "", "112454", "55.0", "2024-03-03T08:00:00+01:00"),
+        ]);
+
+        let reports = fetch_repeat_offender_report(&api, 2).await.unwrap();
+        assert_eq!(reports.len(), 2);
+        let report = &reports[0];
+        assert_eq!(report.registration, "HHAB123");
+        assert_eq!(report.count, 3);
+        assert_eq!(report.charges[0].tbnr, "141322");
+        assert_eq!(report.charges[0].count, 2);
+        assert_eq!(report.charges[0].fine, 40.0);
+        assert_eq!(report.total_fine, 195.0);
+        assert_eq!(report.total_points, 1);
+        assert_eq!(report.locations[0].token, "b");
+        assert_eq!(report.first_seen.to_rfc3339(), "2024-03-01T08:00:00+01:00");
+        assert_eq!(report.last_seen.to_rfc3339(), "2024-04-01T08:00:00+02:00");
+        assert_eq!(reports[1].registration, "BXY1");
+    }
+}
@@ -0,0 +1,585 @@
+use std::{borrow::Borrow, path::Path};
+
+use anyhow::anyhow;
+use chrono::{DateTime, FixedOffset, SecondsFormat};
+use rusqlite::{params, Connection, Transaction};
+
+use crate::types::{charge::Charge, district::District, export::ExportNotice, notice::Notice};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS charges (
+    tbnr TEXT PRIMARY KEY NOT NULL,
+    description TEXT NOT NULL,
+    fine REAL NOT NULL,
+    bkat TEXT NOT NULL,
+    penalty TEXT,
+    fap TEXT,
+    points INTEGER,
+    valid_from TEXT,
+    valid_to TEXT,
+    implementation INTEGER,
+    classification INTEGER NOT NULL,
+    variant_table_id INTEGER,
+    rule_id INTEGER NOT NULL,
+    table_id INTEGER,
+    required_refinements TEXT NOT NULL,
+    number_required_refinements INTEGER NOT NULL,
+    max_fine REAL NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS districts (
+    zip TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    email TEXT NOT NULL,
+    prefixes TEXT NOT NULL,
+    latitude REAL NOT NULL,
+    longitude REAL NOT NULL,
+    aliases TEXT,
+    personal_email INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS notices (
+    token TEXT PRIMARY KEY NOT NULL,
+    status TEXT NOT NULL,
+    street TEXT NOT NULL,
+    city TEXT NOT NULL,
+    zip TEXT NOT NULL,
+    latitude REAL NOT NULL,
+    longitude REAL NOT NULL,
+    registration TEXT NOT NULL,
+    color TEXT NOT NULL,
+    brand TEXT NOT NULL,
+    tbnr TEXT NOT NULL REFERENCES charges (tbnr),
+    start_date TEXT NOT NULL,
+    end_date TEXT NOT NULL,
+    note TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    sent_at TEXT NOT NULL,
+    vehicle_empty INTEGER NOT NULL,
+    hazard_lights INTEGER NOT NULL,
+    expired_tuv INTEGER NOT NULL,
+    expired_eco INTEGER NOT NULL,
+    over_2_8_tons INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS notices_start_date ON notices (start_date);
+CREATE INDEX IF NOT EXISTS notices_tbnr ON notices (tbnr);
+CREATE INDEX IF NOT EXISTS notices_zip ON notices (zip);
+
+CREATE TABLE IF NOT EXISTS photos (
+    notice_token TEXT NOT NULL REFERENCES notices (token) ON DELETE CASCADE,
+    filename TEXT NOT NULL,
+    url TEXT NOT NULL,
+    PRIMARY KEY (notice_token, filename)
+);
+
+CREATE TABLE IF NOT EXISTS export_notices (
+    id INTEGER PRIMARY KEY,
+    export TEXT NOT NULL,
+    start_date TEXT NOT NULL,
+    end_date TEXT NOT NULL,
+    tbnr TEXT NOT NULL,
+    street TEXT NOT NULL,
+    city TEXT NOT NULL,
+    zip TEXT NOT NULL,
+    latitude REAL,
+    longitude REAL
+);
+CREATE INDEX IF NOT EXISTS export_notices_export ON export_notices (export);
+CREATE INDEX IF NOT EXISTS export_notices_start_date ON export_notices (start_date);
+CREATE INDEX IF NOT EXISTS export_notices_tbnr ON export_notices (tbnr);
+CREATE INDEX IF NOT EXISTS export_notices_zip ON export_notices (zip);
+";
+
+/// Timestamps are stored as UTC RFC 3339 strings, so they sort and compare correctly as text
+fn date_time_to_sql(val: &DateTime<FixedOffset>) -> String {
+    val.to_utc().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn strings_to_sql(val: &[String]) -> Result<String, anyhow::Error> {
+    match serde_json::to_string(val) {
+        Err(error) => Err(anyhow!(error)),
+        Ok(val) => Ok(val),
+    }
+}
+
+/// Imports weg.li data into a local SQLite database
+///
+/// Tables are `notices`, `photos`, `charges`, `districts` and `export_notices`. Notices, charges and districts are upserted by their token, tbnr and zip,
+/// so repeated imports update existing rows. Notices reference their charge by tbnr, photos their notice by token.
+/// Timestamps are stored as UTC RFC 3339 text, district prefixes and aliases as JSON arrays.
+pub struct SqliteImporter {
+    connection: Connection,
+}
+
+impl SqliteImporter {
+    /// Open or create the database at `path` and create missing tables and indexes
+    pub fn open(path: &Path) -> Result<Self, anyhow::Error> {
+        match Connection::open(path) {
+            Err(error) => Err(anyhow!(error)),
+            Ok(connection) => SqliteImporter::from_connection(connection),
+        }
+    }
+
+    /// Use an existing connection, e.g. an in-memory database, and create missing tables and indexes
+    pub fn from_connection(connection: Connection) -> Result<Self, anyhow::Error> {
+        if let Err(error) = connection.execute_batch("PRAGMA foreign_keys = ON;") {
+            return Err(anyhow!(error));
+        }
+        if let Err(error) = connection.execute_batch(SCHEMA) {
+            return Err(anyhow!(error));
+        }
+        Ok(SqliteImporter { connection })
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    pub fn into_connection(self) -> Connection {
+        self.connection
+    }
+
+    fn transaction(&mut self) -> Result<Transaction<'_>, anyhow::Error> {
+        match self.connection.transaction() {
+            Err(error) => Err(anyhow!(error)),
+            Ok(val) => Ok(val),
+        }
+    }
+
+    /// Insert or update charges by tbnr
+    pub fn upsert_charges<'a, I>(&mut self, charges: I) -> Result<usize, anyhow::Error>
+    where
+        I: IntoIterator<Item = &'a Charge>,
+    {
+        let transaction = self.transaction()?;
+        let mut count = 0;
+        for charge in charges {
+            upsert_charge(&transaction, charge)?;
+            count += 1;
+        }
+        match transaction.commit() {
+            Err(error) => Err(anyhow!(error)),
+            Ok(_) => Ok(count),
+        }
+    }
+
+    /// Insert or update districts by zip
+    pub fn upsert_districts<'a, I>(&mut self, districts: I) -> Result<usize, anyhow::Error>
+    where
+        I: IntoIterator<Item = &'a District>,
+    {
+        let transaction = self.transaction()?;
+        let mut count = 0;
+        {
+            let mut statement = match transaction.prepare_cached(
+                "INSERT INTO districts (zip, name, email, prefixes, latitude, longitude, aliases, personal_email, created_at, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                ON CONFLICT (zip) DO UPDATE SET
+                    name = excluded.name, email = excluded.email, prefixes = excluded.prefixes,
+                    latitude = excluded.latitude, longitude = excluded.longitude, aliases = excluded.aliases,
+                    personal_email = excluded.personal_email, created_at = excluded.created_at, updated_at = excluded.updated_at",
+            ) {
+                Err(error) => return Err(anyhow!(error)),
+                Ok(val) => val,
+            };
+            for district in districts {
+                let aliases = match &district.aliases {
+                    None => None,
+                    Some(aliases) => Some(strings_to_sql(aliases)?),
+                };
+                if let Err(error) = statement.execute(params![
+                    district.zip,
+                    district.name,
+                    district.email,
+                    strings_to_sql(&district.prefixes)?,
+                    district.latitude,
+                    district.longitude,
+                    aliases,
+                    district.personal_email,
+                    date_time_to_sql(&district.created_at),
+                    date_time_to_sql(&district.updated_at),
+                ]) {
+                    return Err(anyhow!(error));
+                }
+                count += 1;
+            }
+        }
+        match transaction.commit() {
+            Err(error) => Err(anyhow!(error)),
+            Ok(_) => Ok(count),
+        }
+    }
+
+    /// Insert or update notices by token, together with their charge and photos
+    ///
+    /// The photos of an updated notice replace the previously stored ones.
+    pub fn upsert_notices<'a, I>(&mut self, notices: I) -> Result<usize, anyhow::Error>
+    where
+        I: IntoIterator<Item = &'a Notice>,
+    {
+        let transaction = self.transaction()?;
+        let mut count = 0;
+        for notice in notices {
+            upsert_charge(&transaction, &notice.charge)?;
+            upsert_notice(&transaction, notice)?;
+            count += 1;
+        }
+        match transaction.commit() {
+            Err(error) => Err(anyhow!(error)),
+            Ok(_) => Ok(count),
+        }
+    }
+
+    /// Bulk load the rows of one export, identified by `export` (e.g. its download filename)
+    ///
+    /// Rows previously loaded for the same `export` are replaced, rows of other exports are kept.
+    pub fn load_export_notices<I>(
+        &mut self,
+        export: &str,
+        notices: I,
+    ) -> Result<usize, anyhow::Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<ExportNotice>,
+    {
+        let transaction = self.transaction()?;
+        let mut count = 0;
+        if let Err(error) =
+            transaction.execute("DELETE FROM export_notices WHERE export = ?1", [export])
+        {
+            return Err(anyhow!(error));
+        }
+        {
+            let mut statement = match transaction.prepare_cached(
+                "INSERT INTO export_notices (export, start_date, end_date, tbnr, street, city, zip, latitude, longitude)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            ) {
+                Err(error) => return Err(anyhow!(error)),
+                Ok(val) => val,
+            };
+            for notice in notices {
+                let notice = notice.borrow();
+                if let Err(error) = statement.execute(params![
+                    export,
+                    date_time_to_sql(&notice.start_date),
+                    date_time_to_sql(&notice.end_date),
+                    notice.tbnr,
+                    notice.street,
+                    notice.city,
+                    notice.zip,
+                    notice.latitude,
+                    notice.longitude,
+                ]) {
+                    return Err(anyhow!(error));
+                }
+                count += 1;
+            }
+        }
+        match transaction.commit() {
+            Err(error) => Err(anyhow!(error)),
+            Ok(_) => Ok(count),
+        }
+    }
+}
+
+fn upsert_charge(transaction: &Transaction<'_>, charge: &Charge) -> Result<(), anyhow::Error> {
+    let mut statement = match transaction.prepare_cached(
+        "INSERT INTO charges (tbnr, description, fine, bkat, penalty, fap, points, valid_from, valid_to, implementation,
+            classification, variant_table_id, rule_id, table_id, required_refinements, number_required_refinements, max_fine,
+            created_at, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)
+        ON CONFLICT (tbnr) DO UPDATE SET
+            description = excluded.description, fine = excluded.fine, bkat = excluded.bkat, penalty = excluded.penalty,
+            fap = excluded.fap, points = excluded.points, valid_from = excluded.valid_from, valid_to = excluded.valid_to,
+            implementation = excluded.implementation, classification = excluded.classification,
+            variant_table_id = excluded.variant_table_id, rule_id = excluded.rule_id, table_id = excluded.table_id,
+            required_refinements = excluded.required_refinements,
+            number_required_refinements = excluded.number_required_refinements, max_fine = excluded.max_fine,
+            created_at = excluded.created_at, updated_at = excluded.updated_at",
+    ) {
+        Err(error) => return Err(anyhow!(error)),
+        Ok(val) => val,
+    };
+    match statement.execute(params![
+        charge.tbnr,
+        charge.description,
+        charge.fine,
+        charge.bkat,
+        charge.penalty,
+        charge.fap,
+        charge.points,
+        charge.valid_from.as_ref().map(date_time_to_sql),
+        charge.valid_to.as_ref().map(date_time_to_sql),
+        charge.implementation,
+        charge.classification,
+        charge.variant_table_id,
+        charge.rule_id,
+        charge.table_id,
+        charge.required_refinements,
+        charge.number_required_refinements,
+        charge.max_fine,
+        date_time_to_sql(&charge.created_at),
+        date_time_to_sql(&charge.updated_at),
+    ]) {
+        Err(error) => Err(anyhow!(error)),
+        Ok(_) => Ok(()),
+    }
+}
+
+fn upsert_notice(transaction: &Transaction<'_>, notice: &Notice) -> Result<(), anyhow::Error> {
+    let mut statement = match transaction.prepare_cached(
+        "INSERT INTO notices (token, status, street, city, zip, latitude, longitude, registration, color, brand, tbnr,
+            start_date, end_date, note, created_at, updated_at, sent_at, vehicle_empty, hazard_lights, expired_tuv,
+            expired_eco, over_2_8_tons)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)
+        ON CONFLICT (token) DO UPDATE SET
+            status = excluded.status, street = excluded.street, city = excluded.city, zip = excluded.zip,
+            latitude = excluded.latitude, longitude = excluded.longitude, registration = excluded.registration,
+            color = excluded.color, brand = excluded.brand, tbnr = excluded.tbnr, start_date = excluded.start_date,
+            end_date = excluded.end_date, note = excluded.note, created_at = excluded.created_at,
+            updated_at = excluded.updated_at, sent_at = excluded.sent_at, vehicle_empty = excluded.vehicle_empty,
+            hazard_lights = excluded.hazard_lights, expired_tuv = excluded.expired_tuv,
+            expired_eco = excluded.expired_eco, over_2_8_tons = excluded.over_2_8_tons",
+    ) {
+        Err(error) => return Err(anyhow!(error)),
+        Ok(val) => val,
+    };
+    if let Err(error) = statement.execute(params![
+        notice.token,
+        notice.status.to_string(),
+        notice.street,
+        notice.city,
+        notice.zip,
+        notice.latitude,
+        notice.longitude,
+        notice.registration,
+        notice.color,
+        notice.brand,
+        notice.charge.tbnr,
+        date_time_to_sql(&notice.start_date),
+        date_time_to_sql(&notice.end_date),
+        notice.note,
+        date_time_to_sql(&notice.created_at),
+        date_time_to_sql(&notice.updated_at),
+        date_time_to_sql(&notice.sent_at),
+        notice.vehicle_empty,
+        notice.hazard_lights,
+        notice.expired_tuv,
+        notice.expired_eco,
+        notice.over_2_8_tons,
+    ]) {
+        return Err(anyhow!(error));
+    }
+
+    if let Err(error) = transaction.execute(
+        "DELETE FROM photos WHERE notice_token = ?1",
+        [&notice.token],
+    ) {
+        return Err(anyhow!(error));
+    }
+    let mut statement = match transaction
+        .prepare_cached("INSERT INTO photos (notice_token, filename, url) VALUES (?1, ?2, ?3)")
+    {
+        Err(error) => return Err(anyhow!(error)),
+        Ok(val) => val,
+    };
+    for photo in &notice.photos {
+        if let Err(error) = statement.execute(params![notice.token, photo.filename, photo.url]) {
+            return Err(anyhow!(error));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use crate::types::{
+        district::{District, DistrictJson},
+        export::ExportNotice,
+        notice::{Notice, NoticeJson, NoticePhotosJson},
+    };
+
+    use super::SqliteImporter;
+
+    fn notice(photos: &[&str]) -> Notice {
+        let mut notice_json: NoticeJson = serde_json::from_str(
+            r#"{
+                "token": "abc123",
+                "status": "shared",
+                "street": "Hauptstraße 1",
+                "city": "Scheinfeld",
+                "zip": "91443",
+                "latitude": 49.6653406,
+                "longitude": 10.462567,
+                "registration": "NEA AB 123",
+                "color": "silver",
+                "brand": "Opel",
+                "charge": {
+                    "tbnr": "112454",
+                    "description": "Sie parkten verbotswidrig auf dem Gehweg.",
+                    "fine": "55.0",
+                    "bkat": "§ 12 Abs. 4, § 49 StVO; § 24 Abs. 1, 3 Nr. 5 StVG; 52a BKat",
+                    "penalty": null,
+                    "fap": null,
+                    "points": 0,
+                    "valid_from": "2021-11-09T00:00:00.000+01:00",
+                    "valid_to": null,
+                    "implementation": null,
+                    "classification": 5,
+                    "variant_table_id": 712031,
+                    "rule_id": 272,
+                    "table_id": null,
+                    "required_refinements": "00000000000000000000000000000000",
+                    "number_required_refinements": 0,
+                    "max_fine": "0.0",
+                    "created_at": "2023-09-18T15:30:27.417+02:00",
+                    "updated_at": "2023-09-18T15:30:27.417+02:00"
+                },
+                "tbnr": "112454",
+                "start_date": "2023-10-25T09:23:00.000+01:00",
+                "end_date": "2023-10-25T09:41:00.000+01:00",
+                "note": null,
+                "photos": [],
+                "created_at": "2023-10-25T09:23:30.830+01:00",
+                "updated_at": "2023-10-25T09:41:42.638+01:00",
+                "sent_at": "2023-10-25T09:42:32.612+01:00",
+                "vehicle_empty": true,
+                "hazard_lights": false,
+                "expired_tuv": false,
+                "expired_eco": false,
+                "over_2_8_tons": false
+            }"#,
+        )
+        .unwrap();
+        notice_json.photos = photos
+            .iter()
+            .map(|filename| NoticePhotosJson {
+                filename: filename.to_string(),
+                url: format!("https://www.weg.li/storage/{}", filename),
+            })
+            .collect();
+        Notice::try_from(&notice_json).unwrap()
+    }
+
+    #[test]
+    fn test_sqlite_importer() {
+        let mut importer =
+            SqliteImporter::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+
+        let district = District::try_from(&DistrictJson {
+            name: "Scheinfeld".to_string(),
+            zip: "91443".to_string(),
+            email: "info@vgem.scheinfeld.de".to_string(),
+            prefixes: vec!["NEA".to_string(), "SEF".to_string()],
+            latitude: 49.6653406,
+            longitude: 10.462567,
+            aliases: None,
+            personal_email: false,
+            created_at: "2024-03-13T04:43:59.602+01:00".to_string(),
+            updated_at: "2024-03-13T22:12:03.399+01:00".to_string(),
+        })
+        .unwrap();
+        importer.upsert_districts([&district]).unwrap();
+        importer.upsert_districts([&district]).unwrap();
+
+        let start_date =
+            chrono::DateTime::parse_from_rfc3339("2023-10-25T09:23:00.000+01:00").unwrap();
+        let export_notice = ExportNotice {
+            start_date,
+            end_date: start_date,
+            tbnr: "112454".to_string(),
+            street: "Hauptstraße 1".to_string(),
+            city: "Scheinfeld".to_string(),
+            zip: "91443".to_string(),
+            latitude: None,
+            longitude: None,
+        };
+        importer
+            .load_export_notices("notices-46.zip", vec![export_notice.clone(); 2])
+            .unwrap();
+        importer
+            .load_export_notices("notices-47.zip", vec![export_notice.clone(); 3])
+            .unwrap();
+        importer
+            .load_export_notices("notices-47.zip", vec![export_notice; 3])
+            .unwrap();
+
+        let connection = importer.connection();
+        let districts: i64 = connection
+            .query_row("SELECT COUNT(*) FROM districts", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(districts, 1);
+        let (count, start_date): (i64, String) = connection
+            .query_row(
+                "SELECT COUNT(*), MIN(start_date) FROM export_notices JOIN districts USING (zip)",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(count, 5);
+        assert_eq!(start_date, "2023-10-25T08:23:00.000Z");
+    }
+
+    #[test]
+    fn test_sqlite_importer_notices() {
+        let mut importer =
+            SqliteImporter::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+
+        importer
+            .upsert_notices([&notice(&["a.jpg", "b.jpg"])])
+            .unwrap();
+        // the photos of the updated notice replace the stored ones
+        importer.upsert_notices([&notice(&["c.jpg"])]).unwrap();
+
+        let connection = importer.connection();
+        let count = |table: &str| -> i64 {
+            connection
+                .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                    row.get(0)
+                })
+                .unwrap()
+        };
+        assert_eq!(count("notices"), 1);
+        assert_eq!(count("charges"), 1);
+        assert_eq!(count("photos"), 1);
+        let filename: String = connection
+            .query_row("SELECT filename FROM photos", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(filename, "c.jpg");
+
+        // a charge referenced by a notice is updated in place
+        let mut charge = notice(&[]).charge;
+        charge.fine = 70.0;
+        importer.upsert_charges([&charge]).unwrap();
+        let connection = importer.connection();
+        let fine: f64 = connection
+            .query_row(
+                "SELECT fine FROM notices JOIN charges USING (tbnr) WHERE token = 'abc123'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(fine, 70.0);
+
+        // notices need a known charge and photos a known notice
+        assert!(connection
+            .execute(
+                "UPDATE notices SET tbnr = 'unknown' WHERE token = 'abc123'",
+                []
+            )
+            .is_err());
+        assert!(connection
+            .execute(
+                "INSERT INTO photos (notice_token, filename, url) VALUES ('unknown', 'd.jpg', 'd.jpg')",
+                []
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,37 @@
+use std::{fs, path::Path};
+
+use anyhow::anyhow;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Read the JSON state file at `path`, the default state if the file doesn't exist yet
+pub(crate) fn load_state<T: DeserializeOwned + Default>(path: &Path) -> Result<T, anyhow::Error> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let content = match fs::read_to_string(path) {
+        Err(error) => return Err(anyhow!(error)),
+        Ok(val) => val,
+    };
+    match serde_json::from_str(&content) {
+        Err(error) => Err(anyhow!(error)),
+        Ok(val) => Ok(val),
+    }
+}
+
+/// Write `state` as JSON to `path`
+///
+/// The state is written to a temporary file first and renamed, so an interrupted write doesn't corrupt the previous state.
+pub(crate) fn save_state<T: Serialize>(state: &T, path: &Path) -> Result<(), anyhow::Error> {
+    let content = match serde_json::to_string(state) {
+        Err(error) => return Err(anyhow!(error)),
+        Ok(val) => val,
+    };
+    let tmp_path = path.with_extension("tmp");
+    if let Err(error) = fs::write(&tmp_path, content) {
+        return Err(anyhow!(error));
+    }
+    match fs::rename(&tmp_path, path) {
+        Err(error) => Err(anyhow!(error)),
+        Ok(_) => Ok(()),
+    }
+}
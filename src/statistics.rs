@@ -0,0 +1,341 @@
+use std::{collections::BTreeMap, f64::consts::PI, fmt};
+
+use anyhow::anyhow;
+use chrono::Timelike;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::types::{export::ExportNotice, util::street_without_house_number};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CountDimension {
+    Zip,
+    Tbnr,
+    /// Hour of the day of the start date, "00" to "23"
+    HourOfDay,
+    /// Street without house number
+    Street,
+}
+
+impl fmt::Display for CountDimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CountDimension::Zip => write!(f, "zip"),
+            CountDimension::Tbnr => write!(f, "tbnr"),
+            CountDimension::HourOfDay => write!(f, "hour of day"),
+            CountDimension::Street => write!(f, "street"),
+        }
+    }
+}
+
+impl CountDimension {
+    fn key(&self, notice: &ExportNotice) -> String {
+        match self {
+            CountDimension::Zip => notice.zip.clone(),
+            CountDimension::Tbnr => notice.tbnr.clone(),
+            CountDimension::HourOfDay => format!("{:02}", notice.start_date.hour()),
+            CountDimension::Street => street_without_house_number(&notice.street),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NoiseMechanism {
+    /// Laplace noise with scale `1 / epsilon`, for pure epsilon-differential privacy
+    Laplace,
+    /// Gaussian noise for (epsilon, delta)-differential privacy, calibrated for epsilon below 1
+    Gaussian { delta: f64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct PrivacySettings {
+    /// Privacy loss of a single count query, lower means more noise
+    pub epsilon: f64,
+    pub mechanism: NoiseMechanism,
+    /// Total privacy loss allowed over all queries of an [Aggregator]
+    pub budget: f64,
+    /// Keys whose noisy count is below are dropped, unless the keys were given as domain
+    pub min_count: u64,
+}
+
+pub const DEFAULT_PRIVACY_SETTINGS: PrivacySettings = PrivacySettings {
+    epsilon: 0.5,
+    mechanism: NoiseMechanism::Laplace,
+    budget: 2.0,
+    min_count: 10,
+};
+
+/// Number of notices per key of a [CountDimension]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateCounts {
+    pub dimension: CountDimension,
+    pub counts: BTreeMap<String, u64>,
+    /// Privacy loss of this query, `None` for exact counts
+    pub epsilon: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateReport {
+    pub zips: AggregateCounts,
+    pub tbnrs: AggregateCounts,
+    pub hours: AggregateCounts,
+    pub streets: AggregateCounts,
+}
+
+/// Counts export notices per zip, tbnr, hour of day or street, optionally with differentially private noise
+///
+/// Every notice adds one to exactly one key, so noise is calibrated to a sensitivity of 1 per notice.
+/// Each private query spends its epsilon from the budget, and queries exceeding the budget fail.
+///
+/// Without a domain of keys, only keys present in the data are reported, which reveals their presence.
+/// Keys with a small noisy count are dropped to limit this, but passing a public domain, e.g. all zips
+/// of a city, avoids it. Hours of the day always use all 24 hours as domain.
+pub struct Aggregator {
+    privacy: Option<PrivacySettings>,
+    spent: f64,
+    rng: StdRng,
+}
+
+fn laplace_noise(rng: &mut StdRng, scale: f64) -> f64 {
+    // inverse of the cumulative distribution function, avoiding ln(0)
+    let uniform: f64 = rng.gen_range(-0.5..0.5);
+    -scale * uniform.signum() * (1.0 - 2.0 * uniform.abs()).max(f64::MIN_POSITIVE).ln()
+}
+
+fn gaussian_noise(rng: &mut StdRng, standard_deviation: f64) -> f64 {
+    // Box-Muller transform
+    let uniform_a: f64 = 1.0 - rng.gen::<f64>();
+    let uniform_b: f64 = rng.gen();
+    standard_deviation * (-2.0 * uniform_a.ln()).sqrt() * (2.0 * PI * uniform_b).cos()
+}
+
+impl Aggregator {
+    /// Aggregator reporting exact counts
+    pub fn exact() -> Self {
+        Aggregator {
+            privacy: None,
+            spent: 0.0,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    pub fn private(settings: PrivacySettings) -> Result<Self, anyhow::Error> {
+        if !(settings.epsilon > 0.0 && settings.epsilon.is_finite()) {
+            return Err(anyhow!("epsilon must be positive: {}", settings.epsilon));
+        }
+        if let NoiseMechanism::Gaussian { delta } = settings.mechanism {
+            if !(delta > 0.0 && delta < 1.0) {
+                return Err(anyhow!("delta must be between 0 and 1: {}", delta));
+            }
+            // the calibration of the Gaussian noise only holds for epsilon below 1
+            if settings.epsilon >= 1.0 {
+                return Err(anyhow!(
+                    "epsilon must be below 1 for Gaussian noise: {}",
+                    settings.epsilon
+                ));
+            }
+        }
+        if !(settings.budget > 0.0 && settings.budget.is_finite()) {
+            return Err(anyhow!("budget must be positive: {}", settings.budget));
+        }
+        Ok(Aggregator {
+            privacy: Some(settings),
+            spent: 0.0,
+            rng: StdRng::from_entropy(),
+        })
+    }
+
+    /// Draw noise from a generator seeded with `seed`, only for reproducible tests as the noise becomes predictable
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Privacy loss spent by the queries so far
+    pub fn spent_budget(&self) -> f64 {
+        self.spent
+    }
+
+    /// Privacy loss left for further queries, `None` for exact counts
+    pub fn remaining_budget(&self) -> Option<f64> {
+        self.privacy
+            .as_ref()
+            .map(|privacy| (privacy.budget - self.spent).max(0.0))
+    }
+
+    /// Count `notices` per key of `dimension`, only for the keys of `domain` if given
+    pub fn count_by(
+        &mut self,
+        notices: &[ExportNotice],
+        dimension: CountDimension,
+        domain: Option<&[String]>,
+    ) -> Result<AggregateCounts, anyhow::Error> {
+        let mut counts: BTreeMap<String, u64> = BTreeMap::new();
+        let domain: Option<Vec<String>> = match (dimension, domain) {
+            (_, Some(domain)) => Some(domain.to_vec()),
+            (CountDimension::HourOfDay, None) => {
+                Some((0..24).map(|hour| format!("{:02}", hour)).collect())
+            }
+            _ => None,
+        };
+        if let Some(domain) = domain.as_ref() {
+            for key in domain {
+                counts.insert(key.clone(), 0);
+            }
+        }
+        for notice in notices {
+            let key = dimension.key(notice);
+            match counts.get_mut(&key) {
+                Some(count) => *count += 1,
+                None if domain.is_none() => {
+                    counts.insert(key, 1);
+                }
+                None => {}
+            }
+        }
+
+        let privacy = match self.privacy.as_ref() {
+            None => {
+                return Ok(AggregateCounts {
+                    dimension,
+                    counts,
+                    epsilon: None,
+                })
+            }
+            Some(val) => val,
+        };
+        // tolerate rounding errors of summed epsilons
+        if self.spent + privacy.epsilon > privacy.budget + 1e-9 {
+            return Err(anyhow!(
+                "privacy budget exhausted: spent {} of {}, query needs {}",
+                self.spent,
+                privacy.budget,
+                privacy.epsilon
+            ));
+        }
+        self.spent += privacy.epsilon;
+
+        let mut noisy_counts = BTreeMap::new();
+        for (key, count) in counts {
+            let noise = match privacy.mechanism {
+                NoiseMechanism::Laplace => laplace_noise(&mut self.rng, 1.0 / privacy.epsilon),
+                NoiseMechanism::Gaussian { delta } => gaussian_noise(
+                    &mut self.rng,
+                    (2.0 * (1.25 / delta).ln()).sqrt() / privacy.epsilon,
+                ),
+            };
+            // rounding and clamping are post-processing and keep the privacy guarantee
+            let noisy_count = (count as f64 + noise).round().max(0.0) as u64;
+            if domain.is_some() || noisy_count >= privacy.min_count {
+                noisy_counts.insert(key, noisy_count);
+            }
+        }
+        Ok(AggregateCounts {
+            dimension,
+            counts: noisy_counts,
+            epsilon: Some(privacy.epsilon),
+        })
+    }
+
+    /// Count `notices` per zip, tbnr, hour of day and street, spending four times epsilon for private counts
+    pub fn report(&mut self, notices: &[ExportNotice]) -> Result<AggregateReport, anyhow::Error> {
+        if let (Some(privacy), Some(remaining)) = (self.privacy.as_ref(), self.remaining_budget()) {
+            if 4.0 * privacy.epsilon > remaining + 1e-9 {
+                return Err(anyhow!(
+                    "privacy budget exhausted: report needs {}, {} left",
+                    4.0 * privacy.epsilon,
+                    remaining
+                ));
+            }
+        }
+        Ok(AggregateReport {
+            zips: self.count_by(notices, CountDimension::Zip, None)?,
+            tbnrs: self.count_by(notices, CountDimension::Tbnr, None)?,
+            hours: self.count_by(notices, CountDimension::HourOfDay, None)?,
+            streets: self.count_by(notices, CountDimension::Street, None)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use crate::types::export::ExportNotice;
+
+    use super::{
+        Aggregator, CountDimension, NoiseMechanism, PrivacySettings, DEFAULT_PRIVACY_SETTINGS,
+    };
+
+    fn export_notices() -> Vec<ExportNotice> {
+        (0..300)
+            .map(|i| ExportNotice {
+                start_date: DateTime::parse_from_rfc3339(&format!(
+                    "2024-03-01T{:02}:15:00+01:00",
+                    if i % 3 == 0 { 8 } else { 17 }
+                ))
+                .unwrap(),
+                end_date: DateTime::parse_from_rfc3339("2024-03-01T20:00:00+01:00").unwrap(),
+                tbnr: if i < 200 { "112454" } else { "141322" }.to_string(),
+                street: format!("Lange Reihe {}", i % 40),
+                city: "Hamburg".to_string(),
+                zip: if i == 0 { "20097" } else { "20099" }.to_string(),
+                latitude: None,
+                longitude: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_aggregator() {
+        let notices = export_notices();
+        let exact = Aggregator::exact().report(&notices).unwrap();
+        assert_eq!(exact.tbnrs.counts["112454"], 200);
+        assert_eq!(exact.zips.counts["20097"], 1);
+        assert_eq!(exact.hours.counts.len(), 24);
+        assert_eq!(exact.hours.counts["08"], 100);
+        assert_eq!(exact.streets.counts["Lange Reihe"], 300);
+
+        let mut aggregator = Aggregator::private(DEFAULT_PRIVACY_SETTINGS)
+            .unwrap()
+            .with_seed(1);
+        let private = aggregator.report(&notices).unwrap();
+        assert_eq!(aggregator.spent_budget(), 2.0);
+        assert_eq!(private.tbnrs.epsilon, Some(0.5));
+        assert!(private.tbnrs.counts["112454"].abs_diff(200) < 20);
+        // the zip of a single notice is dropped, hours are always reported
+        assert!(!private.zips.counts.contains_key("20097"));
+        assert_eq!(private.hours.counts.len(), 24);
+        assert!(aggregator
+            .count_by(&notices, CountDimension::Zip, None)
+            .is_err());
+
+        let mut aggregator = Aggregator::private(PrivacySettings {
+            epsilon: 0.5,
+            mechanism: NoiseMechanism::Gaussian { delta: 1e-5 },
+            budget: 1.0,
+            min_count: 0,
+        })
+        .unwrap()
+        .with_seed(1);
+        let domain = ["20095".to_string(), "20099".to_string()];
+        let zips = aggregator
+            .count_by(&notices, CountDimension::Zip, Some(&domain))
+            .unwrap();
+        assert_eq!(zips.counts.len(), 2);
+        assert!(zips.counts["20099"].abs_diff(299) < 50);
+        assert_eq!(aggregator.remaining_budget(), Some(0.5));
+        assert!(aggregator.report(&notices).is_err());
+
+        assert!(Aggregator::private(PrivacySettings {
+            epsilon: 1.0,
+            mechanism: NoiseMechanism::Gaussian { delta: 1e-5 },
+            ..DEFAULT_PRIVACY_SETTINGS
+        })
+        .is_err());
+        assert!(Aggregator::private(PrivacySettings {
+            budget: f64::INFINITY,
+            ..DEFAULT_PRIVACY_SETTINGS
+        })
+        .is_err());
+    }
+}
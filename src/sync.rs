@@ -0,0 +1,430 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::anyhow;
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+use crate::{
+    api::wegli_api::WegLiApi,
+    state_file::{load_state, save_state},
+    types::notice::{Notice, NoticeJson, NoticeStatus},
+};
+
+/// Capacity of the event channel, subscribers lagging further behind miss events
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// A single field of a notice that changed between two syncs
+///
+/// Fields of nested objects are named by their path, e.g. `charge.fine`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoticeFieldChange {
+    pub field: String,
+    pub previous: Value,
+    pub current: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NoticeRevisionKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// A change of a notice as observed by [NoticeSync]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoticeRevision {
+    pub kind: NoticeRevisionKind,
+    /// Time of the sync the change was detected in
+    pub observed_at: DateTime<FixedOffset>,
+    /// Update timestamp of the notice as reported by weg.li
+    pub updated_at: DateTime<FixedOffset>,
+    /// Changed fields, only set for [NoticeRevisionKind::Updated]
+    pub changes: Vec<NoticeFieldChange>,
+}
+
+#[derive(Debug, Clone)]
+pub enum NoticeEvent {
+    /// A notice appeared for the first time
+    Created(Notice),
+    /// The status of a notice changed, e.g. from open to shared
+    StatusChanged {
+        token: String,
+        previous: NoticeStatus,
+        current: NoticeStatus,
+    },
+    /// Fields other than the status changed
+    Updated {
+        token: String,
+        changes: Vec<NoticeFieldChange>,
+    },
+    /// A notice is no longer returned by the API, holds its last known state
+    Deleted(Notice),
+}
+
+/// Local copy of the user's notices and their history, serialized as JSON by [NoticeSync]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NoticeStore {
+    /// Latest known state of every notice by token
+    pub notices: BTreeMap<String, NoticeJson>,
+    /// Revisions of every notice by token, oldest first. Kept after a notice is deleted.
+    pub history: BTreeMap<String, Vec<NoticeRevision>>,
+    pub last_synced_at: Option<DateTime<FixedOffset>>,
+}
+
+fn diff_values(
+    prefix: &str,
+    previous: &Value,
+    current: &Value,
+    changes: &mut Vec<NoticeFieldChange>,
+) {
+    match (previous, current) {
+        (Value::Object(previous), Value::Object(current)) => {
+            let mut keys: Vec<&String> = previous.keys().chain(current.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let field = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                diff_values(
+                    &field,
+                    previous.get(key).unwrap_or(&Value::Null),
+                    current.get(key).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        _ => {
+            if previous != current {
+                changes.push(NoticeFieldChange {
+                    field: prefix.to_string(),
+                    previous: previous.clone(),
+                    current: current.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// Field-level differences between two states of a notice
+pub fn diff_notices(previous: &NoticeJson, current: &NoticeJson) -> Vec<NoticeFieldChange> {
+    let mut changes = vec![];
+    // serializing plain data structs does not fail
+    let previous = serde_json::to_value(previous).unwrap_or(Value::Null);
+    let current = serde_json::to_value(current).unwrap_or(Value::Null);
+    diff_values("", &previous, &current, &mut changes);
+    changes
+}
+
+/// Keeps a local store of the authenticated user's notices in sync with the API
+///
+/// Every [sync](NoticeSync::sync) compares the notices by `updated_at`, records a [NoticeRevision] per change
+/// and publishes [NoticeEvent]s to all [subscribers](NoticeSync::subscribe).
+/// If the sync was [opened](NoticeSync::open) with a path, the store is written there after every sync.
+pub struct NoticeSync {
+    store: NoticeStore,
+    path: Option<PathBuf>,
+    events: broadcast::Sender<NoticeEvent>,
+}
+
+impl Default for NoticeSync {
+    fn default() -> Self {
+        NoticeSync::new()
+    }
+}
+
+impl NoticeSync {
+    /// Create a sync keeping its store in memory only
+    pub fn new() -> Self {
+        NoticeSync {
+            store: NoticeStore::default(),
+            path: None,
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+        }
+    }
+
+    /// Create a sync persisting its store as JSON at `path`, loading it if the file exists
+    pub fn open(path: &Path) -> Result<Self, anyhow::Error> {
+        Ok(NoticeSync {
+            store: load_state(path)?,
+            path: Some(path.to_path_buf()),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+        })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<NoticeEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn store(&self) -> &NoticeStore {
+        &self.store
+    }
+
+    /// Revisions of the notice with `token`, oldest first
+    pub fn history(&self, token: &str) -> &[NoticeRevision] {
+        match self.store.history.get(token) {
+            None => &[],
+            Some(revisions) => revisions,
+        }
+    }
+
+    /// Fetch all notices of the authenticated user and apply them to the store
+    pub async fn sync(&mut self, client: &dyn WegLiApi) -> Result<Vec<NoticeEvent>, anyhow::Error> {
+        let notices = match client.get_notices().await {
+            Err(error) => return Err(anyhow!(error)),
+            Ok(val) => val,
+        };
+        self.apply(&notices, Utc::now().fixed_offset())
+    }
+
+    /// Apply the complete list of the user's notices as fetched at `observed_at` to the store
+    ///
+    /// All changes are computed before the store is modified, so on error the store is left as it was.
+    /// Returns the events, which are also sent to all subscribers.
+    pub fn apply(
+        &mut self,
+        notices: &[Notice],
+        observed_at: DateTime<FixedOffset>,
+    ) -> Result<Vec<NoticeEvent>, anyhow::Error> {
+        let mut events = vec![];
+        let mut revisions: Vec<(String, NoticeRevision)> = vec![];
+        let mut upserted: Vec<(String, NoticeJson)> = vec![];
+        let mut seen: HashSet<&str> = HashSet::new();
+
+        for notice in notices {
+            seen.insert(&notice.token);
+            let current = NoticeJson::from(notice);
+            match self.store.notices.get(&notice.token) {
+                None => {
+                    revisions.push((
+                        notice.token.clone(),
+                        NoticeRevision {
+                            kind: NoticeRevisionKind::Created,
+                            observed_at,
+                            updated_at: notice.updated_at,
+                            changes: vec![],
+                        },
+                    ));
+                    events.push(NoticeEvent::Created(notice.clone()));
+                }
+                Some(previous) if previous.updated_at != current.updated_at => {
+                    let changes = diff_notices(previous, &current);
+                    let previous_status = match Notice::try_from(previous) {
+                        Err(error) => return Err(error),
+                        Ok(val) => val.status,
+                    };
+                    if previous_status != notice.status {
+                        events.push(NoticeEvent::StatusChanged {
+                            token: notice.token.clone(),
+                            previous: previous_status,
+                            current: notice.status,
+                        });
+                    }
+                    let other_changes: Vec<NoticeFieldChange> = changes
+                        .iter()
+                        .filter(|change| change.field != "status" && change.field != "updated_at")
+                        .cloned()
+                        .collect();
+                    if !other_changes.is_empty() {
+                        events.push(NoticeEvent::Updated {
+                            token: notice.token.clone(),
+                            changes: other_changes,
+                        });
+                    }
+                    revisions.push((
+                        notice.token.clone(),
+                        NoticeRevision {
+                            kind: NoticeRevisionKind::Updated,
+                            observed_at,
+                            updated_at: notice.updated_at,
+                            changes,
+                        },
+                    ));
+                }
+                Some(_) => continue,
+            }
+            upserted.push((notice.token.clone(), current));
+        }
+
+        let mut deleted: Vec<String> = vec![];
+        for (token, previous) in &self.store.notices {
+            if seen.contains(token.as_str()) {
+                continue;
+            }
+            let notice = Notice::try_from(previous)?;
+            revisions.push((
+                token.clone(),
+                NoticeRevision {
+                    kind: NoticeRevisionKind::Deleted,
+                    observed_at,
+                    updated_at: notice.updated_at,
+                    changes: vec![],
+                },
+            ));
+            events.push(NoticeEvent::Deleted(notice));
+            deleted.push(token.clone());
+        }
+
+        for (token, notice) in upserted {
+            self.store.notices.insert(token, notice);
+        }
+        for token in deleted {
+            self.store.notices.remove(&token);
+        }
+        for (token, revision) in revisions {
+            self.store.history.entry(token).or_default().push(revision);
+        }
+        self.store.last_synced_at = Some(observed_at);
+        self.save()?;
+
+        for event in &events {
+            // sending only fails if there are no subscribers
+            let _ = self.events.send(event.clone());
+        }
+        Ok(events)
+    }
+
+    fn save(&self) -> Result<(), anyhow::Error> {
+        match &self.path {
+            None => Ok(()),
+            Some(path) => save_state(&self.store, path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration};
+
+    use crate::types::notice::{Notice, NoticeJson, NoticeStatus};
+
+    use super::{NoticeEvent, NoticeRevisionKind, NoticeSync};
+
+    fn notice(token: &str) -> Notice {
+        let json = format!(
+            r#"{{
+                "token": "{}",
+                "status": "open",
+                "street": "Hauptstraße 1",
+                "city": "Metropolis",
+                "zip": "12345",
+                "latitude": 71.005523,
+                "longitude": 41.575962,
+                "registration": "XX YYY 123",
+                "color": "silver",
+                "brand": "Chitty Chitty Bang Bang",
+                "charge": {{
+                    "tbnr": "112454",
+                    "description": "Sie parkten verbotswidrig auf dem Gehweg.",
+                    "fine": "55.0",
+                    "bkat": "§ 12 Abs. 4, § 49 StVO; § 24 Abs. 1, 3 Nr. 5 StVG; 52a BKat",
+                    "penalty": null,
+                    "fap": null,
+                    "points": 0,
+                    "valid_from": "2021-11-09T00:00:00.000+01:00",
+                    "valid_to": null,
+                    "implementation": null,
+                    "classification": 5,
+                    "variant_table_id": 712031,
+                    "rule_id": 272,
+                    "table_id": null,
+                    "required_refinements": "00000000000000000000000000000000",
+                    "number_required_refinements": 0,
+                    "max_fine": "0.0",
+                    "created_at": "2023-09-18T15:30:27.417+02:00",
+                    "updated_at": "2023-09-18T15:30:27.417+02:00"
+                }},
+                "tbnr": "112454",
+                "start_date": "2023-10-25T09:23:00.000+01:00",
+                "end_date": "2023-10-25T09:41:00.000+01:00",
+                "note": null,
+                "photos": [],
+                "created_at": "2023-10-25T09:23:30.830+01:00",
+                "updated_at": "2023-10-25T09:41:42.638+01:00",
+                "sent_at": "2023-10-25T09:42:32.612+01:00",
+                "vehicle_empty": true,
+                "hazard_lights": false,
+                "expired_tuv": false,
+                "expired_eco": false,
+                "over_2_8_tons": false
+            }}"#,
+            token
+        );
+        Notice::try_from(&serde_json::from_str(&json).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_notice_sync_apply() {
+        let mut sync = NoticeSync::new();
+        let mut receiver = sync.subscribe();
+        let observed_at = DateTime::parse_from_rfc3339("2023-10-26T00:00:00+01:00").unwrap();
+
+        let events = sync.apply(&[notice("abc123")], observed_at).unwrap();
+        assert!(matches!(&events[..], [NoticeEvent::Created(_)]));
+        assert!(matches!(receiver.try_recv(), Ok(NoticeEvent::Created(_))));
+
+        // unchanged update timestamp, nothing to do
+        let events = sync.apply(&[notice("abc123")], observed_at).unwrap();
+        assert!(events.is_empty());
+
+        let mut shared = notice("abc123");
+        shared.status = NoticeStatus::SHARED;
+        shared.note = Some("Zweites Foto folgt".to_string());
+        shared.updated_at += Duration::days(1);
+        let events = sync.apply(&[shared], observed_at).unwrap();
+        assert!(matches!(
+            &events[0],
+            NoticeEvent::StatusChanged {
+                previous: NoticeStatus::OPEN,
+                current: NoticeStatus::SHARED,
+                ..
+            }
+        ));
+        assert!(
+            matches!(&events[1], NoticeEvent::Updated { changes, .. } if changes.len() == 1 && changes[0].field == "note")
+        );
+
+        let events = sync.apply(&[], observed_at).unwrap();
+        assert!(matches!(&events[..], [NoticeEvent::Deleted(_)]));
+        let kinds: Vec<NoticeRevisionKind> = sync
+            .history("abc123")
+            .iter()
+            .map(|revision| revision.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                NoticeRevisionKind::Created,
+                NoticeRevisionKind::Updated,
+                NoticeRevisionKind::Deleted
+            ]
+        );
+    }
+
+    #[test]
+    fn test_notice_sync_apply_failure_keeps_store() {
+        let mut sync = NoticeSync::new();
+        let observed_at = DateTime::parse_from_rfc3339("2023-10-26T00:00:00+01:00").unwrap();
+        let mut broken = NoticeJson::from(&notice("broken"));
+        broken.status = "unknown".to_string();
+        sync.store.notices.insert("broken".to_string(), broken);
+
+        let mut updated = notice("broken");
+        updated.updated_at += Duration::days(1);
+        // the new notice comes first, the stored state of the second one can't be converted
+        assert!(sync
+            .apply(&[notice("abc123"), updated], observed_at)
+            .is_err());
+        assert!(!sync.store().notices.contains_key("abc123"));
+        assert!(sync.history("abc123").is_empty());
+        assert_eq!(sync.store().notices["broken"].status, "unknown");
+        assert_eq!(sync.store().last_synced_at, None);
+    }
+}
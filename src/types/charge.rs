@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 
 use super::util::{date_time_to_rfc3339, rfc3339_to_date_time};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 /// A [charge](https://www.weg.li/charges) as received by the API call.
 pub struct ChargeJson {
     /// The "Tatbestandsnummer", a unique identifier for the element of an offense ("Tatbestand")
@@ -34,7 +34,7 @@ pub struct ChargeJson {
     pub updated_at: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 /// A [charge](https://www.weg.li/charges) with fields parsed to structured types.
 pub struct Charge {
     /// The "Tatbestandsnummer", a unique identifier for the element of an offense ("Tatbestand")
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 
 use super::util::{date_time_to_rfc3339, rfc3339_to_date_time};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct District {
     pub name: String,
     pub zip: String,
@@ -0,0 +1,48 @@
+use std::{fmt, sync::Arc};
+
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DownloadProgress {
+    /// Number of bytes received and written so far
+    pub bytes_received: u64,
+    /// Size of the download as announced by the server, if known
+    pub total_bytes: Option<u64>,
+}
+
+/// Receiver of [DownloadProgress] updates, called after every chunk written
+#[derive(Clone)]
+pub enum ProgressReporter {
+    Callback(Arc<dyn Fn(DownloadProgress) + Send + Sync>),
+    Channel(watch::Sender<DownloadProgress>),
+}
+
+impl ProgressReporter {
+    pub fn report(&self, progress: DownloadProgress) {
+        match self {
+            ProgressReporter::Callback(callback) => callback(progress),
+            // keeps the latest value even if there is no receiver at the moment
+            ProgressReporter::Channel(sender) => {
+                sender.send_replace(progress);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ProgressReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgressReporter::Callback(_) => f.write_str("ProgressReporter::Callback"),
+            ProgressReporter::Channel(_) => f.write_str("ProgressReporter::Channel"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DownloadOptions {
+    /// Reports progress while downloading if set
+    pub progress: Option<ProgressReporter>,
+    /// Aborts the download, or the extraction of a downloaded archive, when cancelled. Partially written files and a cancelled archive are removed, files that existed before are kept.
+    pub cancellation_token: Option<CancellationToken>,
+}
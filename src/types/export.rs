@@ -4,6 +4,7 @@ use anyhow::anyhow;
 use chrono::{DateTime, FixedOffset};
 use serde::{Deserialize, Serialize};
 
+use super::notice::Notice;
 use super::util::{
     date_time_to_export_timestamp, date_time_to_rfc3339, export_timestamp_to_date_time,
     rfc3339_to_date_time,
@@ -48,6 +49,32 @@ pub struct Export {
     pub download: ExportDownload,
 }
 
+/// Criterion to pick one export out of the exports listed by the API
+#[derive(Debug, Clone)]
+pub enum ExportSelector {
+    /// The most recently created export
+    Latest,
+    /// The most recently created export created strictly before the given timestamp
+    LatestBefore(DateTime<FixedOffset>),
+    /// The export with the given download filename, e.g. `notices-47.zip`
+    Filename(String),
+}
+
+impl ExportSelector {
+    pub fn select<'a>(&self, exports: &'a [Export]) -> Option<&'a Export> {
+        match self {
+            ExportSelector::Latest => exports.iter().max_by_key(|export| export.created_at),
+            ExportSelector::LatestBefore(before) => exports
+                .iter()
+                .filter(|export| &export.created_at < before)
+                .max_by_key(|export| export.created_at),
+            ExportSelector::Filename(filename) => exports
+                .iter()
+                .find(|export| &export.download.filename == filename),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ExportJson {
     pub export_type: String,
@@ -103,7 +130,7 @@ pub struct ExportNoticeCsv {
     pub longitude: Option<f64>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct ExportNotice {
     pub start_date: DateTime<FixedOffset>,
     pub end_date: DateTime<FixedOffset>,
@@ -151,3 +178,18 @@ impl From<&ExportNotice> for ExportNoticeCsv {
         }
     }
 }
+
+impl From<&Notice> for ExportNotice {
+    fn from(value: &Notice) -> Self {
+        ExportNotice {
+            start_date: value.start_date,
+            end_date: value.end_date,
+            tbnr: value.tbnr.clone(),
+            street: value.street.clone(),
+            city: value.city.clone(),
+            zip: value.zip.clone(),
+            latitude: Some(value.latitude),
+            longitude: Some(value.longitude),
+        }
+    }
+}
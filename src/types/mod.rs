@@ -1,6 +1,8 @@
 pub mod charge;
 pub mod district;
+pub mod download;
 pub mod export;
 pub mod notice;
 pub mod request;
-mod util;
+pub mod unzip;
+pub(crate) mod util;
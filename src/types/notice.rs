@@ -15,7 +15,7 @@ pub struct NoticePhotosJson {
     pub url: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NoticeJson {
     /// Token value of the notice
     pub token: String,
@@ -67,7 +67,7 @@ pub struct NoticeJson {
     pub over_2_8_tons: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum NoticeStatus {
     OPEN,
     DISABLED,
@@ -100,7 +100,7 @@ impl ToString for NoticeStatus {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Notice {
     /// Token value of the notice
     pub token: String,
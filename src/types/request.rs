@@ -1,6 +1,87 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::api::rate_limit::RateLimiter;
+
 #[derive(Debug, Clone)]
 pub struct RetrySettings {
     pub max_retries: u32,
     pub initial_backoff_ms: u64,
     pub backoff_multiplier: u64,
 }
+
+/// Group of API endpoints sharing a circuit of the [CircuitBreaker](crate::api::circuit_breaker::CircuitBreaker)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EndpointGroup {
+    Notices,
+    Charges,
+    Districts,
+    Exports,
+}
+
+impl fmt::Display for EndpointGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EndpointGroup::Notices => "notices",
+            EndpointGroup::Charges => "charges",
+            EndpointGroup::Districts => "districts",
+            EndpointGroup::Exports => "exports",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerSettings {
+    /// Number of consecutive failed attempts that opens the circuit
+    pub failure_threshold: u32,
+    /// Time the circuit stays open before trial requests are let through, and the time after which unanswered trials are given up
+    pub cooldown: Duration,
+    /// Number of trial requests let through while half-open
+    pub half_open_max_requests: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitSettings {
+    /// Sustained number of requests per second
+    pub requests_per_second: f64,
+    /// Number of requests that may be sent at once after a quiet period
+    pub burst: u32,
+    /// Lower bound of the rate when it is lowered after throttling
+    pub min_requests_per_second: f64,
+    /// Factor the rate is multiplied with whenever the API responds with 429 or 503
+    pub decrease_factor: f64,
+    /// Requests per second the rate grows by with every successful request, up to `requests_per_second`
+    pub recovery_step: f64,
+}
+
+/// Snapshot of a [RateLimiter]'s state, e.g. to tune its settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimiterStats {
+    /// Rate currently applied, lower than the configured one after throttling
+    pub current_requests_per_second: f64,
+    /// Number of requests let through
+    pub requests: u64,
+    /// Number of times the API signalled to wait
+    pub throttled: u64,
+    /// Time spent waiting for the limiter, summed over all requests
+    pub total_wait: Duration,
+    /// Remaining time all requests are held back because of a `Retry-After` header
+    pub paused_for: Option<Duration>,
+}
+
+/// Options for the bulk helpers like [get_charges_by_tbnr](crate::WegLiApiClient::get_charges_by_tbnr)
+#[derive(Debug, Clone)]
+pub struct BulkOptions {
+    /// Maximum number of requests in flight at the same time
+    pub concurrency: usize,
+    /// Limiter every request waits for before it is sent, in addition to the client's `rate_limiter`
+    pub rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl Default for BulkOptions {
+    fn default() -> Self {
+        BulkOptions {
+            concurrency: 8,
+            rate_limiter: None,
+        }
+    }
+}
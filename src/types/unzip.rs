@@ -0,0 +1,23 @@
+/// What to do when an archive entry would be extracted to an already existing file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Replace the existing file
+    Overwrite,
+    /// Keep the existing file and continue with the next entry
+    Skip,
+    /// Abort extraction with [UnzipError::FileExists](crate::api::error::UnzipError::FileExists)
+    Fail,
+}
+
+#[derive(Debug, Clone)]
+pub struct UnzipLimits {
+    /// Maximum number of bytes extracted from the archive in total
+    pub max_total_bytes: u64,
+    /// Maximum number of entries (files and directories) the archive may contain
+    pub max_entries: usize,
+    /// Maximum ratio of uncompressed to compressed size of a single entry
+    pub max_compression_ratio: u64,
+    /// File extensions extracted files may have, compared case insensitively and without the leading dot. Any extension is allowed if `None`.
+    pub allowed_extensions: Option<Vec<String>>,
+    pub overwrite_policy: OverwritePolicy,
+}
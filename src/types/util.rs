@@ -27,3 +27,16 @@ pub fn export_timestamp_to_date_time(
 pub fn date_time_to_export_timestamp(val: &DateTime<FixedOffset>) -> String {
     format!("{}", val.format(EXPORT_TIMESTAMP_FORMAT))
 }
+
+/// Street of `street` without a trailing house number, e.g. "Hauptstraße" for "Hauptstraße 12a"
+pub fn street_without_house_number(street: &str) -> String {
+    let mut parts: Vec<&str> = street.split_whitespace().collect();
+    while parts.len() > 1
+        && parts
+            .last()
+            .is_some_and(|part| part.chars().any(|c| c.is_ascii_digit()))
+    {
+        parts.pop();
+    }
+    parts.join(" ")
+}
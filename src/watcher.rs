@@ -0,0 +1,345 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    api::wegli_api::WegLiApi,
+    polling::{run_polls, Poll},
+    state_file::{load_state, save_state},
+    types::notice::{Notice, NoticeStatus},
+};
+
+/// A notice whose status differs from the one seen in the previous poll
+#[derive(Debug, Clone)]
+pub struct NoticeStatusChange {
+    /// Status seen in the previous poll, `None` if the notice is new to the watcher
+    pub previous: Option<NoticeStatus>,
+    pub current: NoticeStatus,
+    pub notice: Notice,
+}
+
+pub type NoticeStatusHandler =
+    Arc<dyn Fn(NoticeStatusChange) -> BoxFuture<'static, ()> + Send + Sync>;
+
+pub type NoticeWatcherErrorHandler = Arc<dyn Fn(&anyhow::Error) + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub struct NoticeWatcherSettings {
+    /// Time between two polls
+    pub poll_interval: Duration,
+    /// Notices to watch by token, polled one by one with `get_notice`. All notices of the user are polled with `get_notices` if `None`.
+    pub tokens: Option<Vec<String>>,
+    /// File to keep the last seen statuses in across restarts
+    pub state_path: Option<PathBuf>,
+    /// Whether handlers are called for notices seen for the first time
+    pub notify_new: bool,
+}
+
+pub const DEFAULT_NOTICE_WATCHER_SETTINGS: NoticeWatcherSettings = NoticeWatcherSettings {
+    poll_interval: Duration::from_secs(300),
+    tokens: None,
+    state_path: None,
+    notify_new: false,
+};
+
+/// Last seen status of every watched notice, as persisted in the state file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NoticeWatcherState {
+    pub statuses: BTreeMap<String, String>,
+}
+
+impl NoticeWatcherState {
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        load_state(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), anyhow::Error> {
+        save_state(self, path)
+    }
+}
+
+/// Polls the authenticated user's notices on a schedule and calls handlers when their [NoticeStatus] changes
+///
+/// Requests go through the client, so its retry and backoff settings apply to every poll.
+pub struct NoticeWatcher {
+    settings: NoticeWatcherSettings,
+    state: NoticeWatcherState,
+    handlers: Vec<(Option<NoticeStatus>, NoticeStatusHandler)>,
+    error_handler: Option<NoticeWatcherErrorHandler>,
+}
+
+impl NoticeWatcher {
+    /// Create a watcher, restoring the last seen statuses from `settings.state_path` if the file exists
+    pub fn new(settings: NoticeWatcherSettings) -> Result<Self, anyhow::Error> {
+        let state = match &settings.state_path {
+            None => NoticeWatcherState::default(),
+            Some(path) => NoticeWatcherState::load(path)?,
+        };
+        Ok(NoticeWatcher {
+            settings,
+            state,
+            handlers: vec![],
+            error_handler: None,
+        })
+    }
+
+    pub fn state(&self) -> &NoticeWatcherState {
+        &self.state
+    }
+
+    /// Call `handler` for every status change
+    pub fn on_change<F, Fut>(&mut self, handler: F)
+    where
+        F: Fn(NoticeStatusChange) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.handlers
+            .push((None, Arc::new(move |change| Box::pin(handler(change)))));
+    }
+
+    /// Call `handler` whenever a notice changes to `status`
+    pub fn on_status<F, Fut>(&mut self, status: NoticeStatus, handler: F)
+    where
+        F: Fn(NoticeStatusChange) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.handlers.push((
+            Some(status),
+            Arc::new(move |change| Box::pin(handler(change))),
+        ));
+    }
+
+    /// Call `handler` when a poll fails. The watcher keeps polling in the next interval.
+    pub fn on_error<F>(&mut self, handler: F)
+    where
+        F: Fn(&anyhow::Error) + Send + Sync + 'static,
+    {
+        self.error_handler = Some(Arc::new(handler));
+    }
+
+    async fn fetch(&self, client: &dyn WegLiApi) -> Result<Vec<Notice>, anyhow::Error> {
+        match &self.settings.tokens {
+            None => match client.get_notices().await {
+                Err(error) => Err(anyhow!(error)),
+                Ok(val) => Ok(val),
+            },
+            Some(tokens) => {
+                let mut notices = vec![];
+                for token in tokens {
+                    match client.get_notice(token).await {
+                        Err(error) => return Err(anyhow!(error)),
+                        Ok(val) => notices.push(val),
+                    }
+                }
+                Ok(notices)
+            }
+        }
+    }
+
+    /// Poll once, call the handlers for all status changes and persist the state
+    ///
+    /// The state is saved only after all handlers ran, so if the process stops while handling,
+    /// the changes are handled again after a restart.
+    pub async fn poll(
+        &mut self,
+        client: &dyn WegLiApi,
+    ) -> Result<Vec<NoticeStatusChange>, anyhow::Error> {
+        let notices = self.fetch(client).await?;
+
+        let mut changes = vec![];
+        for notice in notices {
+            let previous = match self.state.statuses.get(&notice.token) {
+                None => None,
+                Some(status) => Some(NoticeStatus::from_str(status)?),
+            };
+            if previous == Some(notice.status) {
+                continue;
+            }
+            self.state
+                .statuses
+                .insert(notice.token.clone(), notice.status.to_string());
+            if previous.is_none() && !self.settings.notify_new {
+                continue;
+            }
+            changes.push(NoticeStatusChange {
+                previous,
+                current: notice.status,
+                notice,
+            });
+        }
+
+        for change in &changes {
+            for (status, handler) in &self.handlers {
+                if status.is_none() || status == &Some(change.current) {
+                    handler(change.clone()).await;
+                }
+            }
+        }
+
+        if let Some(path) = &self.settings.state_path {
+            self.state.save(path)?;
+        }
+        Ok(changes)
+    }
+
+    /// Poll every `poll_interval` until `cancellation_token` is cancelled
+    pub async fn run(&mut self, client: &dyn WegLiApi, cancellation_token: CancellationToken) {
+        let poll_interval = self.settings.poll_interval;
+        run_polls(self, client, poll_interval, cancellation_token).await
+    }
+}
+
+#[async_trait]
+impl Poll for NoticeWatcher {
+    async fn poll_once(&mut self, client: &dyn WegLiApi) -> Result<(), anyhow::Error> {
+        self.poll(client).await.map(|_| ())
+    }
+
+    fn poll_failed(&self, error: &anyhow::Error) {
+        if let Some(error_handler) = &self.error_handler {
+            error_handler(error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use crate::{api::WegLiApiClient, types::notice::NoticeStatus};
+
+    use super::{
+        NoticeWatcher, NoticeWatcherSettings, NoticeWatcherState, DEFAULT_NOTICE_WATCHER_SETTINGS,
+    };
+
+    fn notices_body(status: &str) -> String {
+        format!(
+            r#"[{{
+                "token": "abc123",
+                "status": "{}",
+                "street": "Hauptstraße 1",
+                "city": "Metropolis",
+                "zip": "12345",
+                "latitude": 71.005523,
+                "longitude": 41.575962,
+                "registration": "XX YYY 123",
+                "color": "silver",
+                "brand": "Chitty Chitty Bang Bang",
+                "charge": {{
+                    "tbnr": "112454",
+                    "description": "Sie parkten verbotswidrig auf dem Gehweg.",
+                    "fine": "55.0",
+                    "bkat": "§ 12 Abs. 4, § 49 StVO; § 24 Abs. 1, 3 Nr. 5 StVG; 52a BKat",
+                    "penalty": null,
+                    "fap": null,
+                    "points": 0,
+                    "valid_from": "2021-11-09T00:00:00.000+01:00",
+                    "valid_to": null,
+                    "implementation": null,
+                    "classification": 5,
+                    "variant_table_id": 712031,
+                    "rule_id": 272,
+                    "table_id": null,
+                    "required_refinements": "00000000000000000000000000000000",
+                    "number_required_refinements": 0,
+                    "max_fine": "0.0",
+                    "created_at": "2023-09-18T15:30:27.417+02:00",
+                    "updated_at": "2023-09-18T15:30:27.417+02:00"
+                }},
+                "tbnr": "112454",
+                "start_date": "2023-10-25T09:23:00.000+01:00",
+                "end_date": "2023-10-25T09:41:00.000+01:00",
+                "note": null,
+                "photos": [],
+                "created_at": "2023-10-25T09:23:30.830+01:00",
+                "updated_at": "2023-10-25T09:41:42.638+01:00",
+                "sent_at": "2023-10-25T09:42:32.612+01:00",
+                "vehicle_empty": true,
+                "hazard_lights": false,
+                "expired_tuv": false,
+                "expired_eco": false,
+                "over_2_8_tons": false
+            }}]"#,
+            status
+        )
+    }
+
+    #[tokio::test]
+    async fn test_notice_watcher_poll() {
+        let mut server = mockito::Server::new_async().await;
+        let client = WegLiApiClient::new(&server.url(), &"any_api_key".to_string(), None).unwrap();
+        let state_path = std::env::temp_dir().join("weg_li_api_test_notice_watcher_poll.json");
+        let _ = std::fs::remove_file(&state_path);
+        let settings = NoticeWatcherSettings {
+            state_path: Some(state_path.clone()),
+            ..DEFAULT_NOTICE_WATCHER_SETTINGS
+        };
+
+        let shared_count = Arc::new(AtomicUsize::new(0));
+        let mut watcher = NoticeWatcher::new(settings.clone()).unwrap();
+        let shared_count_clone = shared_count.clone();
+        watcher.on_status(NoticeStatus::SHARED, move |change| {
+            let shared_count = shared_count_clone.clone();
+            async move {
+                assert_eq!(change.previous, Some(NoticeStatus::OPEN));
+                shared_count.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        let mock = server
+            .mock("GET", "/notices")
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(notices_body("open"))
+            .create_async()
+            .await;
+        assert!(watcher.poll(&client).await.unwrap().is_empty());
+        mock.remove_async().await;
+
+        // restart with the persisted state
+        let mut watcher = NoticeWatcher::new(settings).unwrap();
+        let shared_count_clone = shared_count.clone();
+        let state_path_clone = state_path.clone();
+        watcher.on_status(NoticeStatus::SHARED, move |_| {
+            let shared_count = shared_count_clone.clone();
+            // the new status is only saved after the handlers ran
+            let state = NoticeWatcherState::load(&state_path_clone).unwrap();
+            async move {
+                assert_eq!(state.statuses["abc123"], "open");
+                shared_count.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        let mock = server
+            .mock("GET", "/notices")
+            .with_status(200)
+            .with_header("content-type", "application/json; charset=utf-8")
+            .with_body(notices_body("shared"))
+            .expect(2)
+            .create_async()
+            .await;
+        let changes = watcher.poll(&client).await.unwrap();
+        assert_eq!(changes[0].previous, Some(NoticeStatus::OPEN));
+        assert!(watcher.poll(&client).await.unwrap().is_empty());
+        assert_eq!(shared_count.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            NoticeWatcherState::load(&state_path).unwrap().statuses["abc123"],
+            "shared"
+        );
+        mock.assert_async().await;
+        std::fs::remove_file(&state_path).unwrap();
+    }
+}